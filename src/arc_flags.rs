@@ -0,0 +1,398 @@
+//! Arc flags, which prune arcs that do not start a shortest path into the region of the target.
+
+use std::sync::Arc;
+
+use crate::parallel::{number_of_threads, parallel_fold};
+use crate::router::{route_by_index, RouteQuery, RouteResult, Router};
+use crate::{
+    run_search, BastPriorityValue, CompactGraph, DijkstrasAlgorithm, RoutingError, SearchConfig,
+    SearchResult, ZeroPotential,
+};
+
+/// At most this many regions, one bit of a `u64` flag each.
+pub const MAX_REGIONS: usize = 64;
+
+/// Arc flags (Lauther, "An Extremely Fast, Exact Algorithm for Finding Shortest Paths in Static Networks
+/// with Geographical Background"), one bit per region on every arc.
+///
+/// An arc has the bit of a region if it lies on a shortest path into that region,
+/// so a search towards a target only needs the arcs with the bit of the target's region.
+#[derive(Clone, Debug)]
+pub struct ArcFlags {
+    //region of each node
+    regions: Vec<u8>,
+    number_of_regions: usize,
+    //indexed by the edge index of the graph
+    flags: Arc<Vec<u64>>,
+}
+
+/// The arcs a search may relax, see [`crate::SearchConfig::arc_filter`].
+#[derive(Clone, Debug)]
+pub struct ArcFilter {
+    flags: Arc<Vec<u64>>,
+    mask: u64,
+}
+
+impl ArcFilter {
+    /// Whether the search may relax the arc at position `edge` of the graph.
+    pub fn allows(&self, edge: usize) -> bool {
+        self.flags[edge] & self.mask != 0
+    }
+}
+
+//splits the nodes into regions of about the same size, each cut across the longer side of the
+//bounding box of the coordinates (a k-d tree), or into ranges of node indices without coordinates
+fn partition(graph: &CompactGraph, number_of_regions: usize) -> Vec<u8> {
+    let mut regions = vec![0; graph.number_of_nodes()];
+    let nodes: Vec<u32> = (0..graph.number_of_nodes() as u32).collect();
+
+    if graph.coordinates.len() != graph.number_of_nodes() {
+        let region_size = graph.number_of_nodes().div_ceil(number_of_regions).max(1);
+
+        for node in nodes {
+            regions[node as usize] = (node as usize / region_size) as u8;
+        }
+
+        return regions;
+    }
+
+    split(graph, nodes, 0, number_of_regions, &mut regions);
+
+    regions
+}
+
+//gives the regions first_region.. first_region + number_of_regions to nodes
+fn split(
+    graph: &CompactGraph,
+    mut nodes: Vec<u32>,
+    first_region: usize,
+    number_of_regions: usize,
+    regions: &mut [u8],
+) {
+    if number_of_regions <= 1 || nodes.len() <= 1 {
+        for node in nodes {
+            regions[node as usize] = first_region as u8;
+        }
+
+        return;
+    }
+
+    let coordinate = |node: &u32, by_latitude: bool| {
+        let location = graph.coordinates[*node as usize];
+
+        if by_latitude {
+            location.latitude()
+        } else {
+            location.longitude()
+        }
+    };
+
+    let spread = |by_latitude: bool| {
+        let values = nodes.iter().map(|node| coordinate(node, by_latitude));
+
+        values.clone().fold(f64::MIN, f64::max) - values.fold(f64::MAX, f64::min)
+    };
+
+    let by_latitude = spread(true) >= spread(false);
+    nodes.sort_unstable_by(|a, b| {
+        coordinate(a, by_latitude)
+            .total_cmp(&coordinate(b, by_latitude))
+            .then(a.cmp(b))
+    });
+
+    //both halves get nodes in proportion to their number of regions
+    let lower_regions = number_of_regions / 2;
+    let upper = nodes.split_off(nodes.len() * lower_regions / number_of_regions);
+
+    split(graph, nodes, first_region, lower_regions, regions);
+    split(
+        graph,
+        upper,
+        first_region + lower_regions,
+        number_of_regions - lower_regions,
+        regions,
+    );
+}
+
+impl ArcFlags {
+    /// Partitions `graph` into `number_of_regions` regions (at most [`MAX_REGIONS`]) and computes the flags,
+    /// the backward searches are spread over all available threads.
+    pub fn new(graph: &CompactGraph, number_of_regions: usize) -> ArcFlags {
+        ArcFlags::from_partition(
+            graph,
+            partition(graph, number_of_regions.clamp(1, MAX_REGIONS)),
+            number_of_threads(),
+        )
+    }
+
+    /// Flags for a given partition with one region per node, each below [`MAX_REGIONS`].
+    ///
+    /// The result does not depend on the number of threads.
+    pub fn with_regions(
+        graph: &CompactGraph,
+        regions: Vec<u8>,
+        number_of_threads: usize,
+    ) -> Result<ArcFlags, RoutingError> {
+        if regions.len() != graph.number_of_nodes() {
+            return Err(RoutingError::InvalidPartition(
+                "not one region per node of the graph",
+            ));
+        }
+
+        if regions.iter().any(|region| *region as usize >= MAX_REGIONS) {
+            return Err(RoutingError::InvalidPartition("too many regions"));
+        }
+
+        Ok(ArcFlags::from_partition(graph, regions, number_of_threads))
+    }
+
+    fn from_partition(
+        graph: &CompactGraph,
+        regions: Vec<u8>,
+        number_of_threads: usize,
+    ) -> ArcFlags {
+        let number_of_regions = regions
+            .iter()
+            .map(|region| *region as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let region_bit = |node: u32| 1u64 << regions[node as usize];
+
+        let mut flags = vec![0u64; graph.number_of_edges()];
+        let mut boundary_nodes = vec![];
+
+        for tail in 0..graph.number_of_nodes() as u32 {
+            for edge in graph.edge_range(tail) {
+                let head = graph.head[edge];
+
+                if regions[tail as usize] == regions[head as usize] {
+                    //arcs inside a region are needed for every target in it
+                    flags[edge] |= region_bit(head);
+                } else {
+                    //heads of arcs entering a region are where shortest paths from outside come in
+                    boundary_nodes.push(head);
+                }
+            }
+        }
+
+        boundary_nodes.sort_unstable();
+        boundary_nodes.dedup();
+
+        let reversed_graph = graph.reversed();
+        //every thread marks the arcs in its own flags, they are combined at the end
+        let thread_flags = parallel_fold(
+            boundary_nodes.len(),
+            number_of_threads,
+            || vec![0u64; graph.number_of_edges()],
+            |flags, job| {
+                let boundary_node = boundary_nodes[job];
+
+                //distances to the boundary node
+                let distances = run_search(
+                    &reversed_graph,
+                    &mut ZeroPotential,
+                    boundary_node,
+                    &SearchConfig::default(),
+                )
+                .distances;
+
+                //every arc on some shortest path to it, ties included
+                for tail in 0..graph.number_of_nodes() as u32 {
+                    for edge in graph.edge_range(tail) {
+                        let head = graph.head[edge] as usize;
+                        let through = distances[head] + BastPriorityValue::Some(graph.weight[edge]);
+
+                        if through == distances[tail as usize]
+                            && through != BastPriorityValue::Infinity
+                        {
+                            flags[edge] |= region_bit(boundary_node);
+                        }
+                    }
+                }
+            },
+        );
+
+        for thread_flag in thread_flags {
+            for (flag, thread_flag) in flags.iter_mut().zip(thread_flag) {
+                *flag |= thread_flag;
+            }
+        }
+
+        ArcFlags {
+            regions,
+            number_of_regions,
+            flags: Arc::new(flags),
+        }
+    }
+
+    /// How many regions the graph was partitioned into.
+    pub fn number_of_regions(&self) -> usize {
+        self.number_of_regions
+    }
+
+    /// The region the node index `node` belongs to.
+    pub fn region(&self, node: u32) -> u8 {
+        self.regions[node as usize]
+    }
+
+    /// Only the arcs towards the region of `target`.
+    pub fn filter_for(&self, target: u32) -> ArcFilter {
+        ArcFilter {
+            flags: self.flags.clone(),
+            mask: 1u64 << self.region(target),
+        }
+    }
+
+    /// Share of the arcs with the flag of the region, how many a search towards it can use.
+    pub fn flagged_share(&self, region: u8) -> Result<f64, RoutingError> {
+        if region as usize >= self.number_of_regions {
+            return Err(RoutingError::RegionOutOfRange(region));
+        }
+
+        let flagged = self
+            .flags
+            .iter()
+            .filter(|flag| **flag & (1u64 << region) != 0)
+            .count();
+
+        Ok(flagged as f64 / self.flags.len().max(1) as f64)
+    }
+}
+
+/// Dijkstra's algorithm which only relaxes the arcs flagged for the region of the target.
+pub struct ArcFlagsRouter {
+    graph: CompactGraph,
+    arc_flags: ArcFlags,
+}
+
+impl ArcFlagsRouter {
+    /// Partitions `graph` into `number_of_regions` regions and computes the flags.
+    pub fn new(graph: CompactGraph, number_of_regions: usize) -> ArcFlagsRouter {
+        let arc_flags = ArcFlags::new(&graph, number_of_regions);
+
+        ArcFlagsRouter::with_arc_flags(graph, arc_flags)
+    }
+
+    /// Uses flags which were already computed on `graph`.
+    pub fn with_arc_flags(graph: CompactGraph, arc_flags: ArcFlags) -> ArcFlagsRouter {
+        ArcFlagsRouter { graph, arc_flags }
+    }
+
+    /// The graph the router searches.
+    pub fn graph(&self) -> &CompactGraph {
+        &self.graph
+    }
+
+    /// The flags the router prunes its searches with.
+    pub fn arc_flags(&self) -> &ArcFlags {
+        &self.arc_flags
+    }
+
+    /// The search between the node indices `source` and `target`,
+    /// [`SearchResult::pruned_arcs`] tells how many arcs the flags removed from it.
+    pub fn search(&self, source: u32, target: u32) -> Result<SearchResult, RoutingError> {
+        self.graph.check_node(source)?;
+        self.graph.check_node(target)?;
+
+        let config = SearchConfig {
+            arc_filter: Some(self.arc_flags.filter_for(target)),
+            ..SearchConfig::to_target(target)
+        };
+
+        DijkstrasAlgorithm::search_graph(&self.graph, source, &config)
+    }
+}
+
+impl Router for ArcFlagsRouter {
+    fn route(&mut self, query: &RouteQuery) -> Result<RouteResult, RoutingError> {
+        route_by_index(&self.graph, query, |source, target| {
+            let result = self.search(source, target)?;
+
+            Ok((result.path_to(&self.graph, target)?, result.settled.len()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{
+        assert_same_routes, coordinate_grid_test_graph, oneway_grid_test_graph,
+    };
+
+    #[test]
+    fn arc_flags_match_dijkstra() {
+        //a grid with coordinates is cut by the k-d tree, the one-way grid by node index
+        for road_network in [coordinate_grid_test_graph(12), oneway_grid_test_graph(12)] {
+            let graph = CompactGraph::from(&road_network);
+
+            let mut arc_flags = ArcFlagsRouter::new(graph.clone(), 8);
+
+            assert_eq!(arc_flags.arc_flags().number_of_regions(), 8);
+
+            let (settled_dijkstra, settled_arc_flags) =
+                assert_same_routes(&mut DijkstrasAlgorithm::new(graph), &mut arc_flags, 144);
+
+            let mut pruned_arcs = 0;
+            for source in (0..144).step_by(5) {
+                for target in (0..144).step_by(7) {
+                    pruned_arcs += arc_flags.search(source, target).unwrap().pruned_arcs;
+                }
+            }
+
+            assert!(matches!(
+                arc_flags.search(0, 144),
+                Err(RoutingError::NodeIndexOutOfRange(144))
+            ));
+
+            assert!(pruned_arcs > 0);
+            assert!(settled_arc_flags < settled_dijkstra);
+        }
+    }
+
+    #[test]
+    fn partition_and_threads() {
+        let graph = CompactGraph::from(&oneway_grid_test_graph(10));
+
+        let one_thread = ArcFlags::with_regions(&graph, partition(&graph, 4), 1).unwrap();
+        let four_threads = ArcFlags::with_regions(&graph, partition(&graph, 4), 4).unwrap();
+        assert_eq!(one_thread.flags, four_threads.flags);
+
+        //ranges of node indices of equal size
+        assert_eq!(one_thread.region(0), 0);
+        assert_eq!(one_thread.region(99), 3);
+        assert_eq!(
+            (0..100)
+                .filter(|node| one_thread.region(*node) == 1)
+                .count(),
+            25
+        );
+
+        for region in 0..4 {
+            assert!(one_thread.flagged_share(region).unwrap() < 1.0);
+        }
+
+        for region in [4, MAX_REGIONS as u8, u8::MAX] {
+            assert!(matches!(
+                one_thread.flagged_share(region),
+                Err(RoutingError::RegionOutOfRange(out_of_range)) if out_of_range == region
+            ));
+        }
+
+        assert!(matches!(
+            ArcFlags::with_regions(&graph, vec![0; 99], 1),
+            Err(RoutingError::InvalidPartition(_))
+        ));
+
+        let mut too_many_regions = partition(&graph, 4);
+        too_many_regions[7] = MAX_REGIONS as u8;
+        assert!(matches!(
+            ArcFlags::with_regions(&graph, too_many_regions, 1),
+            Err(RoutingError::InvalidPartition(_))
+        ));
+
+        //without a filter nothing is pruned
+        let result = DijkstrasAlgorithm::search_graph(&graph, 0, &SearchConfig::default()).unwrap();
+        assert_eq!(result.pruned_arcs, 0);
+    }
+}
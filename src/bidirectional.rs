@@ -0,0 +1,460 @@
+//! Searches from the source and from the target at the same time.
+
+use priority_queue::DoublePriorityQueue;
+use std::sync::Arc;
+
+use crate::landmarks::{check_landmark_database, landmark_bound};
+use crate::router::{route_by_index, RouteQuery, RouteResult, Router};
+use crate::{
+    precompute_landmark_distances, BastPriorityValue, CompactGraph, LandmarkDatabase,
+    LandmarkSelection, RoutingError, ShortestPath,
+};
+
+/// Dijkstra's algorithm from both ends, forward from the source and backward from the target
+/// on the reversed graph, until the two searches prove that no shorter path is left.
+pub struct BidirectionalDijkstra {
+    /// The graph the forward search runs on.
+    pub graph: CompactGraph,
+    //the backward search runs on it
+    reversed_graph: CompactGraph,
+}
+
+/// What a bidirectional search found, with the work of each side.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BidirectionalResult {
+    /// The route, None if the target cannot be reached.
+    pub path: Option<ShortestPath>,
+    /// How many nodes the search from the source settled.
+    pub forward_settled: usize,
+    /// How many nodes the search from the target settled.
+    pub backward_settled: usize,
+}
+
+/// Bidirectional A* with landmark bounds, both sides use the average of the two landmark potentials
+/// (Ikeda et al., "A fast algorithm for finding better routes by AI search techniques").
+///
+/// The forward search follows `(π_t(v) - π_s(v)) / 2` and the backward search its negation,
+/// where `π_t` bounds the distance to the target and `π_s` the distance from the source.
+/// Both searches see the same reduced edge costs, so they stop as early as plain bidirectional Dijkstra.
+pub struct BidirectionalAlt {
+    /// The graph the forward search runs on.
+    pub graph: CompactGraph,
+    reversed_graph: CompactGraph,
+    landmark_database: Arc<LandmarkDatabase>,
+    memo: PotentialMemo,
+}
+
+//potentials of the current query, values[node] is only valid if marks[node] == round,
+//so a new query does not have to clear it
+struct PotentialMemo {
+    values: Vec<i64>,
+    marks: Vec<usize>,
+    round: usize,
+}
+
+//one of the two searches, distances are from the source (forward) or to the target (backward)
+struct SearchSide {
+    //keys are twice the distance plus the potential of the side, they can be negative
+    pq: DoublePriorityQueue<u32, i64>,
+    distances: Vec<BastPriorityValue>,
+    //towards the node the side started from
+    prev: Vec<Option<u32>>,
+    settled: usize,
+}
+
+impl SearchSide {
+    fn new(number_of_nodes: usize, start: u32, key: i64) -> SearchSide {
+        let mut side = SearchSide {
+            pq: DoublePriorityQueue::new(),
+            distances: vec![BastPriorityValue::Infinity; number_of_nodes],
+            prev: vec![None; number_of_nodes],
+            settled: 0,
+        };
+
+        side.distances[start as usize] = BastPriorityValue::Some(0);
+        side.pq.push(start, key);
+
+        side
+    }
+
+    //None once the side has settled everything it can reach
+    fn min_key(&self) -> Option<i64> {
+        self.pq.peek_min().map(|(_, key)| *key)
+    }
+}
+
+//bidirectional Dijkstra where the forward side is guided by potential and the backward side by its negation,
+//potential(v) has to be twice a feasible forward potential, e.g. π_t(v) - π_s(v)
+fn run_bidirectional_search(
+    graph: &CompactGraph,
+    reversed_graph: &CompactGraph,
+    source: u32,
+    target: u32,
+    mut potential: impl FnMut(u32) -> i64,
+) -> BidirectionalResult {
+    let number_of_nodes = graph.number_of_nodes();
+
+    let mut forward = SearchSide::new(number_of_nodes, source, potential(source));
+    let mut backward = SearchSide::new(number_of_nodes, target, -potential(target));
+
+    //cost of the best path seen so far and the node where its two halves meet
+    let mut best = BastPriorityValue::Infinity;
+    let mut meeting_node = None;
+
+    if source == target {
+        best = BastPriorityValue::Some(0);
+        meeting_node = Some(source);
+    }
+
+    while let (Some(forward_key), Some(backward_key)) = (forward.min_key(), backward.min_key()) {
+        //every path not seen yet has twice its cost at least the smallest keys of both sides together,
+        //the potentials cancel out
+        if let BastPriorityValue::Some(best) = best {
+            if forward_key + backward_key >= 2 * best as i64 {
+                break;
+            }
+        }
+
+        //grow the side with the smaller key
+        let (side, other, graph, sign) = if forward_key <= backward_key {
+            (&mut forward, &backward, graph, 1)
+        } else {
+            (&mut backward, &forward, reversed_graph, -1)
+        };
+
+        let (u, _) = side.pq.pop_min().unwrap();
+        let u_dist = side.distances[u as usize];
+        side.settled += 1;
+
+        for (v, cost) in graph.neighbours(u) {
+            let alt = u_dist + BastPriorityValue::Some(cost);
+
+            if alt < side.distances[v as usize] {
+                side.distances[v as usize] = alt;
+                side.prev[v as usize] = Some(u);
+
+                if let BastPriorityValue::Some(alt) = alt {
+                    side.pq.push(v, 2 * alt as i64 + sign * potential(v));
+                }
+            }
+
+            //the other side may have reached v after this side did, so check it every time
+            let through = side.distances[v as usize] + other.distances[v as usize];
+
+            if through < best {
+                best = through;
+                meeting_node = Some(v);
+            }
+        }
+    }
+
+    let path = meeting_node.map(|meeting_node| {
+        //source to the meeting node, then on to the target
+        let mut path = vec![meeting_node];
+        let mut current = meeting_node;
+
+        while let Some(previous) = forward.prev[current as usize] {
+            path.push(previous);
+            current = previous;
+        }
+
+        path.reverse();
+        current = meeting_node;
+
+        while let Some(next) = backward.prev[current as usize] {
+            path.push(next);
+            current = next;
+        }
+
+        ShortestPath::from_node_indices(graph, path)
+    });
+
+    BidirectionalResult {
+        path,
+        forward_settled: forward.settled,
+        backward_settled: backward.settled,
+    }
+}
+
+impl BidirectionalResult {
+    //the path and the nodes both sides settled together, as route_by_index takes them
+    pub(crate) fn into_route(self) -> (Option<ShortestPath>, usize) {
+        (self.path, self.forward_settled + self.backward_settled)
+    }
+}
+
+impl BidirectionalDijkstra {
+    /// Searches `graph` and its reversed copy.
+    pub fn new(graph: CompactGraph) -> BidirectionalDijkstra {
+        BidirectionalDijkstra {
+            reversed_graph: graph.reversed(),
+            graph,
+        }
+    }
+
+    /// Shortest path between the node indices `source` and `target`.
+    pub fn search(&self, source: u32, target: u32) -> Result<BidirectionalResult, RoutingError> {
+        self.graph.check_node(source)?;
+        self.graph.check_node(target)?;
+
+        Ok(run_bidirectional_search(
+            &self.graph,
+            &self.reversed_graph,
+            source,
+            target,
+            |_| 0,
+        ))
+    }
+}
+
+impl Router for BidirectionalDijkstra {
+    fn route(&mut self, query: &RouteQuery) -> Result<RouteResult, RoutingError> {
+        route_by_index(&self.graph, query, |source, target| {
+            self.search(source, target)
+                .map(BidirectionalResult::into_route)
+        })
+    }
+}
+
+impl BidirectionalAlt {
+    /// Landmarks as in [`crate::AltRouter::new`].
+    pub fn new(graph: CompactGraph, number_of_landmarks: usize) -> BidirectionalAlt {
+        let landmark_database = precompute_landmark_distances(
+            &graph,
+            number_of_landmarks,
+            LandmarkSelection::default(),
+        );
+
+        BidirectionalAlt::with_landmark_database(graph, landmark_database)
+    }
+
+    /// See [`crate::AltRouter::with_landmark_database`].
+    pub fn with_landmark_database(
+        graph: CompactGraph,
+        landmark_database: LandmarkDatabase,
+    ) -> BidirectionalAlt {
+        BidirectionalAlt {
+            reversed_graph: graph.reversed(),
+            memo: PotentialMemo {
+                values: vec![0; graph.number_of_nodes()],
+                marks: vec![0; graph.number_of_nodes()],
+                round: 0,
+            },
+            graph,
+            landmark_database: Arc::new(landmark_database),
+        }
+    }
+
+    /// Shortest path between the node indices `source` and `target`,
+    /// fails if the landmark distances do not cover them.
+    pub fn search(
+        &mut self,
+        source: u32,
+        target: u32,
+    ) -> Result<BidirectionalResult, RoutingError> {
+        alt_search(
+            &self.graph,
+            &self.reversed_graph,
+            &self.landmark_database,
+            &mut self.memo,
+            source,
+            target,
+        )
+    }
+
+    /// The landmark distances both potentials are computed from.
+    pub fn landmark_database(&self) -> &LandmarkDatabase {
+        &self.landmark_database
+    }
+}
+
+impl Router for BidirectionalAlt {
+    fn route(&mut self, query: &RouteQuery) -> Result<RouteResult, RoutingError> {
+        route_by_index(&self.graph, query, |source, target| {
+            alt_search(
+                &self.graph,
+                &self.reversed_graph,
+                &self.landmark_database,
+                &mut self.memo,
+                source,
+                target,
+            )
+            .map(BidirectionalResult::into_route)
+        })
+    }
+}
+
+//a free function, so the router can search while route_by_index holds on to the graph
+fn alt_search(
+    graph: &CompactGraph,
+    reversed_graph: &CompactGraph,
+    landmark_database: &LandmarkDatabase,
+    memo: &mut PotentialMemo,
+    source: u32,
+    target: u32,
+) -> Result<BidirectionalResult, RoutingError> {
+    let number_of_nodes = graph.number_of_nodes();
+
+    check_landmark_database(number_of_nodes, landmark_database, source)?;
+    check_landmark_database(number_of_nodes, landmark_database, target)?;
+
+    //each node is bounded once per query
+    memo.round += 1;
+
+    Ok(run_bidirectional_search(
+        graph,
+        reversed_graph,
+        source,
+        target,
+        |node| {
+            let node_index = node as usize;
+
+            if memo.marks[node_index] != memo.round {
+                memo.values[node_index] =
+                    average_potential(landmark_database, source, target, node);
+                memo.marks[node_index] = memo.round;
+            }
+
+            memo.values[node_index]
+        },
+    ))
+}
+
+//π_t(node) - π_s(node), twice the forward potential so it stays an integer
+fn average_potential(
+    landmark_database: &LandmarkDatabase,
+    source: u32,
+    target: u32,
+    node: u32,
+) -> i64 {
+    let number_of_landmarks = landmark_database.number_of_landmarks();
+
+    let to_target = landmark_bound(landmark_database, 0..number_of_landmarks, node, target);
+    let from_source = landmark_bound(landmark_database, 0..number_of_landmarks, source, node);
+
+    to_target as i64 - from_source as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{
+        assert_same_routes, grid_test_graph, oneway_grid_test_graph, small_test_graph,
+    };
+    use crate::{DijkstrasAlgorithm, SearchConfig};
+
+    #[test]
+    fn bidirectional_matches_dijkstra() {
+        for graph in [grid_test_graph(12), oneway_grid_test_graph(12)] {
+            let graph = CompactGraph::from(&graph);
+
+            let (settled_dijkstra, settled_bidirectional) = assert_same_routes(
+                &mut DijkstrasAlgorithm::new(graph.clone()),
+                &mut BidirectionalDijkstra::new(graph),
+                144,
+            );
+
+            assert!(settled_bidirectional < settled_dijkstra);
+        }
+    }
+
+    #[test]
+    fn bidirectional_alt_matches_dijkstra() {
+        for graph in [grid_test_graph(12), oneway_grid_test_graph(12)] {
+            let graph = CompactGraph::from(&graph);
+            let landmark_database =
+                precompute_landmark_distances(&graph, 8, LandmarkSelection::Avoid { seed: 5 });
+
+            let (settled_bidirectional, settled_alt) = assert_same_routes(
+                &mut BidirectionalDijkstra::new(graph.clone()),
+                &mut BidirectionalAlt::with_landmark_database(graph, landmark_database),
+                144,
+            );
+
+            assert!(settled_alt < settled_bidirectional);
+        }
+    }
+
+    #[test]
+    fn average_potentials_are_consistent() {
+        let graph = CompactGraph::from(&oneway_grid_test_graph(10));
+        let landmark_database =
+            precompute_landmark_distances(&graph, 4, LandmarkSelection::Farthest { seed: 1 });
+
+        for (source, target) in [(0, 99), (99, 0), (43, 57)] {
+            let potential = |node| average_potential(&landmark_database, source, target, node);
+
+            let from_source =
+                DijkstrasAlgorithm::search_graph(&graph, source, &SearchConfig::default()).unwrap();
+            let to_target = DijkstrasAlgorithm::search_graph(
+                &graph.reversed(),
+                target,
+                &SearchConfig::default(),
+            )
+            .unwrap();
+
+            //the reduced cost of every edge is the same for both sides and never negative on the edges
+            //a path from the source to the target can use, the bounds are 0 for unreachable nodes
+            for tail in (0..100).filter(|tail| from_source.is_settled(*tail).unwrap()) {
+                for (head, cost) in graph.neighbours(tail) {
+                    if to_target.is_settled(head).unwrap() {
+                        assert!(2 * cost as i64 + potential(head) - potential(tail) >= 0);
+                    }
+                }
+            }
+        }
+
+        let smaller = CompactGraph::from(&grid_test_graph(3));
+        let mut alt = BidirectionalAlt::with_landmark_database(smaller, landmark_database);
+        assert!(matches!(
+            alt.search(0, 8),
+            Err(RoutingError::NodeCountMismatch {
+                expected: 9,
+                found: 100
+            })
+        ));
+    }
+
+    #[test]
+    fn settled_nodes_per_side() {
+        let graph = CompactGraph::from(&small_test_graph());
+        let mut bidirectional = BidirectionalDijkstra::new(graph.clone());
+
+        let result = bidirectional.search(0, 2).unwrap();
+        let path = result.path.unwrap();
+
+        assert_eq!(path.path, vec![1, 2, 3]);
+        assert_eq!(path.edge_costs, vec![4, 1]);
+        assert_eq!(path.cost, 5);
+        assert!(result.forward_settled > 0);
+        assert!(result.backward_settled > 0);
+
+        let result = bidirectional.search(1, 1).unwrap();
+        assert_eq!(result.path.unwrap().path, vec![2]);
+        assert_eq!(result.forward_settled + result.backward_settled, 0);
+
+        assert!(matches!(
+            bidirectional.search(0, 5),
+            Err(RoutingError::NodeIndexOutOfRange(5))
+        ));
+
+        //node 4 has no edges
+        assert!(matches!(
+            bidirectional.route(&RouteQuery {
+                source: 1,
+                target: 4
+            }),
+            Err(RoutingError::UnreachableTarget {
+                source: 1,
+                target: 4
+            })
+        ));
+        assert!(matches!(
+            bidirectional.route(&RouteQuery {
+                source: 0,
+                target: 1
+            }),
+            Err(RoutingError::UnknownNode(0))
+        ));
+    }
+}
@@ -0,0 +1,416 @@
+//! The adjacency array graph all queries run on.
+
+use geoutils::Location;
+use rand::Rng;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::error::RoutingError;
+use crate::metric::CostUnit;
+use crate::road_network::RoadNetwork;
+
+/// A directed graph stored as adjacency arrays (compressed sparse row).
+///
+/// Nodes are numbered densely from 0, `osm_ids` maps the index back to the OSM node id.
+/// The outgoing edges of node `u` are `head[e]` / `weight[e]` for `e` in `edge_range(u)`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompactGraph {
+    /// Node index -> OSM id, sorted ascending so the numbering is the same on every run.
+    pub osm_ids: Vec<i64>,
+    /// OSM id -> node index.
+    pub indices: HashMap<i64, u32>,
+    /// `number_of_nodes + 1` offsets into `head` and `weight`.
+    pub first_out: Vec<u32>,
+    /// Node index each edge leads to.
+    pub head: Vec<u32>,
+    /// Cost of each edge.
+    pub weight: Vec<u32>,
+    /// Lat/lon indexed by node, empty if the graph was built without coordinates.
+    pub coordinates: Vec<Location>,
+    /// What the edge costs are measured in.
+    pub unit: CostUnit,
+    /// Fastest speed on any edge in km/h, 0 if unknown.
+    pub max_speed_kmh: f32,
+}
+
+impl CompactGraph {
+    /// Builds the graph from a list of `(tail, head, weight)` edges given by node index.
+    pub fn from_edges(osm_ids: Vec<i64>, mut edges: Vec<(u32, u32, u32)>) -> CompactGraph {
+        edges.sort_unstable();
+
+        let mut first_out: Vec<u32> = vec![0; osm_ids.len() + 1];
+
+        for (tail, _, _) in edges.iter() {
+            first_out[*tail as usize + 1] += 1;
+        }
+
+        for i in 0..osm_ids.len() {
+            first_out[i + 1] += first_out[i];
+        }
+
+        let indices = osm_ids
+            .iter()
+            .enumerate()
+            .map(|(index, osm_id)| (*osm_id, index as u32))
+            .collect();
+
+        CompactGraph {
+            osm_ids,
+            indices,
+            first_out,
+            head: edges.iter().map(|edge| edge.1).collect(),
+            weight: edges.iter().map(|edge| edge.2).collect(),
+            coordinates: vec![],
+            unit: CostUnit::default(),
+            max_speed_kmh: 0.0,
+        }
+    }
+
+    /// Nodes are numbered `0..number_of_nodes()`.
+    pub fn number_of_nodes(&self) -> usize {
+        self.osm_ids.len()
+    }
+
+    /// Directed edges, an edge in both directions counts twice.
+    pub fn number_of_edges(&self) -> usize {
+        self.head.len()
+    }
+
+    /// Node index of the OSM node id, None if it is not part of the graph.
+    pub fn index_of(&self, osm_id: i64) -> Option<u32> {
+        self.indices.get(&osm_id).copied()
+    }
+
+    /// Fails with [`RoutingError::NodeIndexOutOfRange`] unless `node` is a node index of the graph.
+    pub(crate) fn check_node(&self, node: u32) -> Result<(), RoutingError> {
+        if node as usize >= self.number_of_nodes() {
+            return Err(RoutingError::NodeIndexOutOfRange(node));
+        }
+
+        Ok(())
+    }
+
+    /// OSM node id of the node index, panics if it is out of range.
+    pub fn osm_id(&self, node: u32) -> i64 {
+        self.osm_ids[node as usize]
+    }
+
+    /// Lat/lon of the node index, None if the graph has no coordinates.
+    pub fn coordinate(&self, node: u32) -> Option<Location> {
+        self.coordinates.get(node as usize).copied()
+    }
+
+    /// Positions in `head` and `weight` of the edges leaving `node`.
+    pub fn edge_range(&self, node: u32) -> Range<usize> {
+        self.first_out[node as usize] as usize..self.first_out[node as usize + 1] as usize
+    }
+
+    /// `(head, weight)` of every edge leaving `node`.
+    pub fn neighbours(&self, node: u32) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.edge_range(node)
+            .map(move |edge| (self.head[edge], self.weight[edge]))
+    }
+
+    /// The cheapest edge from `tail` to `head`, if there is one.
+    pub fn edge_weight(&self, tail: u32, head: u32) -> Option<u32> {
+        self.neighbours(tail)
+            .filter(|(neighbour, _)| *neighbour == head)
+            .map(|(_, weight)| weight)
+            .min()
+    }
+
+    /// The same nodes with every edge turned around.
+    pub fn reversed(&self) -> CompactGraph {
+        let edges = (0..self.number_of_nodes() as u32)
+            .flat_map(|tail| {
+                self.neighbours(tail)
+                    .map(move |(head, weight)| (head, tail, weight))
+            })
+            .collect();
+
+        CompactGraph {
+            coordinates: self.coordinates.clone(),
+            unit: self.unit,
+            max_speed_kmh: self.max_speed_kmh,
+            ..CompactGraph::from_edges(self.osm_ids.clone(), edges)
+        }
+    }
+
+    /// FNV-1a hash of the node ids, the edges and the unit, identifies the graph data was computed on.
+    pub fn checksum(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+        let mut hash = FNV_OFFSET_BASIS;
+        let mut add = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        };
+
+        add(&(self.osm_ids.len() as u64).to_le_bytes());
+        add(&(self.head.len() as u64).to_le_bytes());
+        add(&[self.unit as u8]);
+
+        for osm_id in self.osm_ids.iter() {
+            add(&osm_id.to_le_bytes());
+        }
+
+        for value in self
+            .first_out
+            .iter()
+            .chain(self.head.iter())
+            .chain(self.weight.iter())
+        {
+            add(&value.to_le_bytes());
+        }
+
+        hash
+    }
+
+    /// Only the nodes for which `keep` is true and the edges between them, renumbered.
+    pub fn subgraph(&self, keep: impl Fn(u32) -> bool) -> CompactGraph {
+        let mut new_index: Vec<Option<u32>> = vec![None; self.number_of_nodes()];
+        let mut osm_ids: Vec<i64> = vec![];
+
+        for node in 0..self.number_of_nodes() as u32 {
+            if keep(node) {
+                new_index[node as usize] = Some(osm_ids.len() as u32);
+                osm_ids.push(self.osm_id(node));
+            }
+        }
+
+        let mut edges: Vec<(u32, u32, u32)> = vec![];
+
+        for tail in 0..self.number_of_nodes() as u32 {
+            for (head, weight) in self.neighbours(tail) {
+                if let (Some(new_tail), Some(new_head)) =
+                    (new_index[tail as usize], new_index[head as usize])
+                {
+                    edges.push((new_tail, new_head, weight));
+                }
+            }
+        }
+
+        let coordinates = match self.coordinates.is_empty() {
+            true => vec![],
+            false => (0..self.number_of_nodes() as u32)
+                .filter(|node| new_index[*node as usize].is_some())
+                .map(|node| self.coordinates[node as usize])
+                .collect(),
+        };
+
+        CompactGraph {
+            coordinates,
+            unit: self.unit,
+            max_speed_kmh: self.max_speed_kmh,
+            ..CompactGraph::from_edges(osm_ids, edges)
+        }
+    }
+
+    /// Strongly connected component of every node, numbered from 0 (Tarjan's algorithm).
+    pub fn strongly_connected_components(&self) -> Vec<u32> {
+        const UNVISITED: u32 = u32::MAX;
+
+        let number_of_nodes = self.number_of_nodes();
+
+        let mut index: Vec<u32> = vec![UNVISITED; number_of_nodes];
+        let mut lowlink: Vec<u32> = vec![0; number_of_nodes];
+        let mut on_stack: Vec<bool> = vec![false; number_of_nodes];
+        let mut stack: Vec<u32> = vec![];
+        let mut component: Vec<u32> = vec![UNVISITED; number_of_nodes];
+
+        let mut next_index: u32 = 0;
+        let mut next_component: u32 = 0;
+
+        //explicit recursion stack of (node, next edge to look at), roads are too long for the call stack
+        let mut call_stack: Vec<(u32, usize)> = vec![];
+
+        for root in 0..number_of_nodes as u32 {
+            if index[root as usize] != UNVISITED {
+                continue;
+            }
+
+            index[root as usize] = next_index;
+            lowlink[root as usize] = next_index;
+            next_index += 1;
+            stack.push(root);
+            on_stack[root as usize] = true;
+            call_stack.push((root, self.edge_range(root).start));
+
+            while let Some((node, edge)) = call_stack.last().copied() {
+                if edge < self.edge_range(node).end {
+                    call_stack.last_mut().unwrap().1 += 1;
+
+                    let head = self.head[edge];
+
+                    if index[head as usize] == UNVISITED {
+                        index[head as usize] = next_index;
+                        lowlink[head as usize] = next_index;
+                        next_index += 1;
+                        stack.push(head);
+                        on_stack[head as usize] = true;
+                        call_stack.push((head, self.edge_range(head).start));
+                    } else if on_stack[head as usize] {
+                        lowlink[node as usize] = lowlink[node as usize].min(index[head as usize]);
+                    }
+                } else {
+                    call_stack.pop();
+
+                    if let Some((parent, _)) = call_stack.last() {
+                        lowlink[*parent as usize] =
+                            lowlink[*parent as usize].min(lowlink[node as usize]);
+                    }
+
+                    //node is the root of a component, everything above it on the stack belongs to it
+                    if lowlink[node as usize] == index[node as usize] {
+                        while let Some(member) = stack.pop() {
+                            on_stack[member as usize] = false;
+                            component[member as usize] = next_component;
+
+                            if member == node {
+                                break;
+                            }
+                        }
+
+                        next_component += 1;
+                    }
+                }
+            }
+        }
+
+        component
+    }
+
+    /// OSM node id of a node picked uniformly at random.
+    pub fn get_random_node(&self) -> i64 {
+        let index = rand::thread_rng().gen_range(0..self.number_of_nodes());
+        self.osm_ids[index]
+    }
+}
+
+/// Every node of the road network becomes a node of the graph, as does every end of its edges,
+/// even if it was added to `edges` without going through [`RoadNetwork::insert_edge`].
+impl From<&RoadNetwork> for CompactGraph {
+    fn from(road_network: &RoadNetwork) -> CompactGraph {
+        let mut osm_ids: Vec<i64> = road_network
+            .nodes
+            .iter()
+            .chain(
+                road_network
+                    .edges
+                    .iter()
+                    .flat_map(|(tail, heads)| std::iter::once(tail).chain(heads.keys())),
+            )
+            .cloned()
+            .collect();
+        osm_ids.sort_unstable();
+        osm_ids.dedup();
+
+        let indices: HashMap<i64, u32> = osm_ids
+            .iter()
+            .enumerate()
+            .map(|(index, osm_id)| (*osm_id, index as u32))
+            .collect();
+
+        let edges = road_network
+            .edges
+            .iter()
+            .flat_map(|(tail, heads)| {
+                let tail = indices[tail];
+                heads
+                    .iter()
+                    .map(|(head, cost)| (tail, indices[head], *cost))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        //only keep coordinates if every node has them, so they can be indexed by node
+        let coordinates = osm_ids
+            .iter()
+            .map(|osm_id| road_network.coordinate(*osm_id))
+            .collect::<Option<Vec<Location>>>()
+            .unwrap_or_default();
+
+        CompactGraph {
+            coordinates,
+            unit: road_network.unit,
+            max_speed_kmh: road_network.max_speed_kmh,
+            ..CompactGraph::from_edges(osm_ids, edges)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::small_test_graph;
+
+    #[test]
+    fn conversion_from_road_network() {
+        let road_network = small_test_graph();
+        let graph = CompactGraph::from(&road_network);
+
+        assert_eq!(graph.osm_ids, vec![1, 2, 3, 4]);
+        assert_eq!(graph.number_of_edges(), 6);
+        assert_eq!(graph.first_out, vec![0, 2, 4, 6, 6]);
+        assert_eq!(graph.coordinate(2), road_network.coordinate(3));
+        assert_eq!(graph.coordinate(2), Some(Location::new(48.002, 9.0)));
+
+        for (tail, heads) in road_network.edges.iter() {
+            for (head, cost) in heads.iter() {
+                let tail = graph.index_of(*tail).unwrap();
+                let head = graph.index_of(*head).unwrap();
+
+                assert_eq!(graph.edge_weight(tail, head), Some(*cost));
+            }
+        }
+
+        let reversed = graph.reversed();
+        assert_eq!(reversed.edge_weight(2, 0), graph.edge_weight(0, 2));
+
+        let without_two = graph.subgraph(|node| graph.osm_id(node) != 2);
+        assert_eq!(without_two.osm_ids, vec![1, 3, 4]);
+        assert_eq!(without_two.coordinate(1), road_network.coordinate(3));
+        assert_eq!(without_two.edge_weight(0, 1), Some(7));
+        assert_eq!(without_two.number_of_edges(), 2);
+    }
+
+    #[test]
+    fn edge_ends_missing_from_the_nodes() {
+        let mut road_network = small_test_graph();
+        road_network.edges.entry(3).or_default().insert(5, 2);
+
+        let graph = CompactGraph::from(&road_network);
+
+        assert_eq!(graph.osm_ids, vec![1, 2, 3, 4, 5]);
+        assert_eq!(graph.edge_weight(2, 4), Some(2));
+        //node 5 has no coordinate
+        assert!(graph.coordinates.is_empty());
+    }
+
+    #[test]
+    fn strongly_connected_components() {
+        //0 <-> 1 -> 2 <-> 3, 4 -> 0
+        let graph = CompactGraph::from_edges(
+            vec![10, 11, 12, 13, 14],
+            vec![
+                (0, 1, 1),
+                (1, 0, 1),
+                (1, 2, 1),
+                (2, 3, 1),
+                (3, 2, 1),
+                (4, 0, 1),
+            ],
+        );
+
+        let components = graph.strongly_connected_components();
+
+        assert_eq!(components[0], components[1]);
+        assert_eq!(components[2], components[3]);
+        assert_ne!(components[0], components[2]);
+        assert_ne!(components[4], components[0]);
+        assert_ne!(components[4], components[2]);
+    }
+}
@@ -0,0 +1,611 @@
+//! Contraction Hierarchies, shortcuts that let both searches only go upwards.
+
+use priority_queue::DoublePriorityQueue;
+use std::collections::HashMap;
+
+use crate::router::{route_by_index, RouteQuery, RouteResult, Router};
+use crate::{
+    BastPriorityValue, BidirectionalResult, CompactGraph, RoadNetwork, RoutingError, ShortestPath,
+};
+
+//a witness search gives up after settling this many nodes and the shortcut is added
+const WITNESS_SETTLED_LIMIT: usize = 500;
+
+/// Contraction Hierarchies (Geisberger et al., "Contraction Hierarchies: Faster and Simpler
+/// Hierarchical Routing in Road Networks").
+///
+/// The nodes are contracted one after another, cheapest edge difference first, and shortcuts keep
+/// the distances between the remaining nodes. A query only follows edges towards nodes contracted later,
+/// forward from the source and backward from the target, and unpacks the shortcuts of the path it found.
+pub struct ContractionHierarchy {
+    /// The graph the hierarchy was built on, without shortcuts.
+    pub graph: CompactGraph,
+    //position of each node in the contraction order, indexed by node
+    rank: Vec<u32>,
+    //edges from each node to nodes of higher rank
+    upward: UpwardGraph,
+    //edges into each node from nodes of higher rank, turned around
+    downward: UpwardGraph,
+}
+
+//(head, weight, the node a shortcut skips or None for an edge of the graph)
+type ChEdge = (u32, u32, Option<u32>);
+
+//edges of one direction grouped by node
+struct UpwardGraph {
+    first_out: Vec<u32>,
+    edges: Vec<ChEdge>,
+}
+
+impl UpwardGraph {
+    fn from_lists(lists: Vec<Vec<ChEdge>>) -> UpwardGraph {
+        let mut first_out = Vec::with_capacity(lists.len() + 1);
+        first_out.push(0);
+
+        let mut edges = vec![];
+
+        for list in lists {
+            edges.extend(list);
+            first_out.push(edges.len() as u32);
+        }
+
+        UpwardGraph { first_out, edges }
+    }
+
+    fn edges(&self, node: u32) -> &[ChEdge] {
+        &self.edges
+            [self.first_out[node as usize] as usize..self.first_out[node as usize + 1] as usize]
+    }
+
+    //there is at most one edge per pair of nodes
+    fn find(&self, node: u32, head: u32) -> ChEdge {
+        *self.edges(node).iter().find(|edge| edge.0 == head).unwrap()
+    }
+}
+
+//the graph while it is contracted, only edges between nodes which are not contracted yet
+struct ContractionGraph {
+    outgoing: Vec<Vec<ChEdge>>,
+    //(tail, weight, middle)
+    incoming: Vec<Vec<ChEdge>>,
+    //how many neighbours of each node are contracted already
+    deleted_neighbours: Vec<i64>,
+    //depth of the hierarchy below each node
+    level: Vec<i64>,
+}
+
+impl ContractionGraph {
+    fn new(graph: &CompactGraph) -> ContractionGraph {
+        let number_of_nodes = graph.number_of_nodes();
+
+        let mut contraction_graph = ContractionGraph {
+            outgoing: vec![vec![]; number_of_nodes],
+            incoming: vec![vec![]; number_of_nodes],
+            deleted_neighbours: vec![0; number_of_nodes],
+            level: vec![0; number_of_nodes],
+        };
+
+        for tail in 0..number_of_nodes as u32 {
+            for (head, weight) in graph.neighbours(tail) {
+                //loops are never part of a shortest path
+                if head != tail {
+                    contraction_graph.insert_edge(tail, head, weight, None);
+                }
+            }
+        }
+
+        contraction_graph
+    }
+
+    //keeps the cheaper edge if there already is one between the nodes
+    fn insert_edge(&mut self, tail: u32, head: u32, weight: u32, middle: Option<u32>) {
+        match self.outgoing[tail as usize]
+            .iter()
+            .position(|edge| edge.0 == head)
+        {
+            Some(position) => {
+                if weight < self.outgoing[tail as usize][position].1 {
+                    self.outgoing[tail as usize][position] = (head, weight, middle);
+
+                    let incoming = &mut self.incoming[head as usize];
+                    let position = incoming.iter().position(|edge| edge.0 == tail).unwrap();
+                    incoming[position] = (tail, weight, middle);
+                }
+            }
+            None => {
+                self.outgoing[tail as usize].push((head, weight, middle));
+                self.incoming[head as usize].push((tail, weight, middle));
+            }
+        }
+    }
+
+    //the shortcuts (tail, head, weight) contracting node needs, the ones without a witness path
+    fn shortcuts(&self, node: u32, witness: &mut WitnessSearch) -> Vec<(u32, u32, u32)> {
+        let mut shortcuts = vec![];
+
+        for &(tail, in_weight, _) in &self.incoming[node as usize] {
+            let outgoing = &self.outgoing[node as usize];
+
+            let Some(max_cost) = outgoing
+                .iter()
+                .filter(|edge| edge.0 != tail)
+                .map(|edge| in_weight + edge.1)
+                .max()
+            else {
+                continue;
+            };
+
+            witness.run(self, tail, node, max_cost);
+
+            for &(head, out_weight, _) in outgoing {
+                if head != tail
+                    && witness.distance(head) > BastPriorityValue::Some(in_weight + out_weight)
+                {
+                    shortcuts.push((tail, head, in_weight + out_weight));
+                }
+            }
+        }
+
+        shortcuts
+    }
+
+    //lower is contracted first: the edge difference, spread over the graph by the contracted
+    //neighbours and kept shallow by the level
+    fn priority(&self, node: u32, witness: &mut WitnessSearch) -> i64 {
+        let edge_difference = self.shortcuts(node, witness).len() as i64
+            - (self.incoming[node as usize].len() + self.outgoing[node as usize].len()) as i64;
+
+        2 * edge_difference + self.deleted_neighbours[node as usize] + self.level[node as usize]
+    }
+
+    //takes node out of the graph, returns its remaining outgoing and incoming edges
+    fn contract(&mut self, node: u32, witness: &mut WitnessSearch) -> (Vec<ChEdge>, Vec<ChEdge>) {
+        for (tail, head, weight) in self.shortcuts(node, witness) {
+            self.insert_edge(tail, head, weight, Some(node));
+        }
+
+        let outgoing = std::mem::take(&mut self.outgoing[node as usize]);
+        let incoming = std::mem::take(&mut self.incoming[node as usize]);
+
+        for &(head, _, _) in &outgoing {
+            self.incoming[head as usize].retain(|edge| edge.0 != node);
+        }
+
+        for &(tail, _, _) in &incoming {
+            self.outgoing[tail as usize].retain(|edge| edge.0 != node);
+        }
+
+        for neighbour in outgoing.iter().chain(incoming.iter()).map(|edge| edge.0) {
+            self.deleted_neighbours[neighbour as usize] += 1;
+            self.level[neighbour as usize] =
+                self.level[neighbour as usize].max(self.level[node as usize] + 1);
+        }
+
+        (outgoing, incoming)
+    }
+}
+
+//local Dijkstra between the neighbours of the node which is contracted, reused between searches
+struct WitnessSearch {
+    distances: Vec<BastPriorityValue>,
+    //nodes whose distance has to be reset before the next search
+    touched: Vec<u32>,
+    pq: DoublePriorityQueue<u32, BastPriorityValue>,
+}
+
+impl WitnessSearch {
+    fn new(number_of_nodes: usize) -> WitnessSearch {
+        WitnessSearch {
+            distances: vec![BastPriorityValue::Infinity; number_of_nodes],
+            touched: vec![],
+            pq: DoublePriorityQueue::new(),
+        }
+    }
+
+    //distances from source without passing through avoid, exact up to max_cost unless the limit is hit
+    fn run(&mut self, graph: &ContractionGraph, source: u32, avoid: u32, max_cost: u32) {
+        for node in self.touched.drain(..) {
+            self.distances[node as usize] = BastPriorityValue::Infinity;
+        }
+        self.pq.clear();
+
+        self.distances[source as usize] = BastPriorityValue::Some(0);
+        self.touched.push(source);
+        self.pq.push(source, BastPriorityValue::Some(0));
+
+        let mut settled = 0;
+
+        while let Some((u, u_dist)) = self.pq.pop_min() {
+            settled += 1;
+
+            if u_dist > BastPriorityValue::Some(max_cost) || settled > WITNESS_SETTLED_LIMIT {
+                break;
+            }
+
+            for &(v, weight, _) in &graph.outgoing[u as usize] {
+                let alt = u_dist + BastPriorityValue::Some(weight);
+
+                if v != avoid && alt < self.distances[v as usize] {
+                    if self.distances[v as usize] == BastPriorityValue::Infinity {
+                        self.touched.push(v);
+                    }
+
+                    self.distances[v as usize] = alt;
+                    self.pq.push(v, alt);
+                }
+            }
+        }
+    }
+
+    fn distance(&self, node: u32) -> BastPriorityValue {
+        self.distances[node as usize]
+    }
+}
+
+//one side of a query, the search spaces are small so only the reached nodes are stored
+#[derive(Default)]
+struct UpwardSearch {
+    pq: DoublePriorityQueue<u32, u32>,
+    //distance and the edge it was reached by as (tail, index into the UpwardGraph edges of tail)
+    reached: HashMap<u32, (u32, Option<(u32, usize)>)>,
+    settled: usize,
+}
+
+impl UpwardSearch {
+    fn new(start: u32) -> UpwardSearch {
+        let mut search = UpwardSearch::default();
+
+        search.reached.insert(start, (0, None));
+        search.pq.push(start, 0);
+
+        search
+    }
+
+    fn distance(&self, node: u32) -> Option<u32> {
+        self.reached.get(&node).map(|(distance, _)| *distance)
+    }
+
+    //settles the next node, returns it with its distance
+    fn settle_next(&mut self, graph: &UpwardGraph) -> (u32, u32) {
+        self.settle_next_unless(graph, |_| false)
+    }
+
+    //like settle_next(), but the edges of a node stop() holds for are not followed
+    fn settle_next_unless(
+        &mut self,
+        graph: &UpwardGraph,
+        stop: impl Fn(u32) -> bool,
+    ) -> (u32, u32) {
+        let (u, u_dist) = self.pq.pop_min().unwrap();
+        self.settled += 1;
+
+        if stop(u) {
+            return (u, u_dist);
+        }
+
+        let first_edge = graph.first_out[u as usize] as usize;
+
+        for (offset, &(v, weight, _)) in graph.edges(u).iter().enumerate() {
+            let alt = u_dist + weight;
+
+            if self.distance(v).is_none_or(|distance| alt < distance) {
+                self.reached
+                    .insert(v, (alt, Some((u, first_edge + offset))));
+                self.pq.push(v, alt);
+            }
+        }
+
+        (u, u_dist)
+    }
+
+    //the edges from the start to node, as indices into the UpwardGraph edges
+    fn edges_to(&self, node: u32) -> Vec<(u32, usize)> {
+        let mut edges = vec![];
+        let mut current = node;
+
+        while let Some((_, Some((previous, edge)))) = self.reached.get(&current) {
+            edges.push((*previous, *edge));
+            current = *previous;
+        }
+
+        edges.reverse();
+        edges
+    }
+}
+
+impl ContractionHierarchy {
+    /// Contracts every node of `graph`, this is the expensive part.
+    pub fn new(graph: CompactGraph) -> ContractionHierarchy {
+        let number_of_nodes = graph.number_of_nodes();
+
+        let mut contraction_graph = ContractionGraph::new(&graph);
+        let mut witness = WitnessSearch::new(number_of_nodes);
+
+        let mut queue: DoublePriorityQueue<u32, i64> = DoublePriorityQueue::new();
+        for node in 0..number_of_nodes as u32 {
+            queue.push(node, contraction_graph.priority(node, &mut witness));
+        }
+
+        let mut rank = vec![0; number_of_nodes];
+        let mut upward = vec![vec![]; number_of_nodes];
+        let mut downward = vec![vec![]; number_of_nodes];
+        let mut next_rank = 0;
+
+        while let Some((node, _)) = queue.pop_min() {
+            //lazy update, the priority may have grown since it was computed
+            let priority = contraction_graph.priority(node, &mut witness);
+
+            if queue
+                .peek_min()
+                .is_some_and(|(_, next_priority)| priority > *next_priority)
+            {
+                queue.push(node, priority);
+                continue;
+            }
+
+            let (outgoing, incoming) = contraction_graph.contract(node, &mut witness);
+
+            rank[node as usize] = next_rank;
+            next_rank += 1;
+
+            for neighbour in outgoing.iter().chain(incoming.iter()).map(|edge| edge.0) {
+                if queue.get_priority(&neighbour).is_some() {
+                    queue.change_priority(
+                        &neighbour,
+                        contraction_graph.priority(neighbour, &mut witness),
+                    );
+                }
+            }
+
+            //all of them lead to nodes which are contracted later
+            upward[node as usize] = outgoing;
+            downward[node as usize] = incoming;
+        }
+
+        ContractionHierarchy {
+            graph,
+            rank,
+            upward: UpwardGraph::from_lists(upward),
+            downward: UpwardGraph::from_lists(downward),
+        }
+    }
+
+    /// Position of the node index in the contraction order.
+    pub fn rank(&self, node: u32) -> u32 {
+        self.rank[node as usize]
+    }
+
+    /// How many edges of the hierarchy skip a node.
+    pub fn number_of_shortcuts(&self) -> usize {
+        self.upward
+            .edges
+            .iter()
+            .chain(self.downward.edges.iter())
+            .filter(|edge| edge.2.is_some())
+            .count()
+    }
+
+    /// Cost of the shortest path between the node indices `source` and `target`
+    /// without unpacking it.
+    pub fn distance(&self, source: u32, target: u32) -> Result<BastPriorityValue, RoutingError> {
+        let (best, _, _, _) = self.run_query(source, target)?;
+        Ok(best)
+    }
+
+    //every node the upward search from start settles with its distance, on the edges towards
+    //higher ranks (forward) or on the turned around edges from higher ranks (backward)
+    pub(crate) fn search_space(&self, start: u32, forward: bool) -> Vec<(u32, u32)> {
+        self.search_space_below(start, forward, |_| false)
+    }
+
+    //like search_space(), but the search does not go on from the nodes stop() holds for,
+    //they are still part of the result
+    pub(crate) fn search_space_below(
+        &self,
+        start: u32,
+        forward: bool,
+        stop: impl Fn(u32) -> bool,
+    ) -> Vec<(u32, u32)> {
+        let graph = if forward {
+            &self.upward
+        } else {
+            &self.downward
+        };
+        let mut search = UpwardSearch::new(start);
+        let mut settled = vec![];
+
+        while !search.pq.is_empty() {
+            settled.push(search.settle_next_unless(graph, &stop));
+        }
+
+        settled
+    }
+
+    /// Shortest path between the node indices `source` and `target` with every shortcut unpacked.
+    pub fn search(&self, source: u32, target: u32) -> Result<BidirectionalResult, RoutingError> {
+        let (_, meeting_node, forward, backward) = self.run_query(source, target)?;
+
+        let path = meeting_node.map(|meeting_node| {
+            let mut path = vec![source];
+
+            for (tail, edge) in forward.edges_to(meeting_node) {
+                let (head, _, middle) = self.upward.edges[edge];
+                self.unpack(tail, head, middle, &mut path);
+            }
+
+            //the backward edges are turned around, they lead from the meeting node down to the target
+            for (head, edge) in backward.edges_to(meeting_node).into_iter().rev() {
+                let (tail, _, middle) = self.downward.edges[edge];
+                self.unpack(tail, head, middle, &mut path);
+            }
+
+            ShortestPath::from_node_indices(&self.graph, path)
+        });
+
+        Ok(BidirectionalResult {
+            path,
+            forward_settled: forward.settled,
+            backward_settled: backward.settled,
+        })
+    }
+
+    //upward searches from both ends, returns the cost, the node where they meet and both search spaces
+    fn run_query(
+        &self,
+        source: u32,
+        target: u32,
+    ) -> Result<(BastPriorityValue, Option<u32>, UpwardSearch, UpwardSearch), RoutingError> {
+        self.graph.check_node(source)?;
+        self.graph.check_node(target)?;
+
+        let mut forward = UpwardSearch::new(source);
+        let mut backward = UpwardSearch::new(target);
+
+        let mut best = BastPriorityValue::Infinity;
+        let mut meeting_node = None;
+
+        loop {
+            //a side is done once its next node is further away than the best path
+            let forward_key = forward.pq.peek_min().map(|(_, key)| *key);
+            let backward_key = backward.pq.peek_min().map(|(_, key)| *key);
+
+            let active =
+                |key: Option<u32>| key.is_some_and(|key| BastPriorityValue::Some(key) < best);
+
+            let (side, other, graph) = match (active(forward_key), active(backward_key)) {
+                (false, false) => break,
+                //the closer side first while both are active
+                (true, false) => (&mut forward, &backward, &self.upward),
+                (true, true) if forward_key <= backward_key => {
+                    (&mut forward, &backward, &self.upward)
+                }
+                _ => (&mut backward, &forward, &self.downward),
+            };
+
+            let (u, u_dist) = side.settle_next(graph);
+
+            if let Some(other_dist) = other.distance(u) {
+                let through = BastPriorityValue::Some(u_dist + other_dist);
+
+                if through < best {
+                    best = through;
+                    meeting_node = Some(u);
+                }
+            }
+        }
+
+        Ok((best, meeting_node, forward, backward))
+    }
+
+    //appends the nodes after tail up to head of the edge tail -> head to path
+    fn unpack(&self, tail: u32, head: u32, middle: Option<u32>, path: &mut Vec<u32>) {
+        match middle {
+            None => path.push(head),
+            //both halves were edges of middle when it was contracted
+            Some(middle) => {
+                let (_, _, first_middle) = self.downward.find(middle, tail);
+                let (_, _, second_middle) = self.upward.find(middle, head);
+
+                self.unpack(tail, middle, first_middle, path);
+                self.unpack(middle, head, second_middle, path);
+            }
+        }
+    }
+}
+
+impl From<&RoadNetwork> for ContractionHierarchy {
+    fn from(road_network: &RoadNetwork) -> ContractionHierarchy {
+        ContractionHierarchy::new(CompactGraph::from(road_network))
+    }
+}
+
+impl Router for ContractionHierarchy {
+    fn route(&mut self, query: &RouteQuery) -> Result<RouteResult, RoutingError> {
+        route_by_index(&self.graph, query, |source, target| {
+            self.search(source, target)
+                .map(BidirectionalResult::into_route)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{
+        assert_same_routes, grid_test_graph, oneway_grid_test_graph, small_test_graph,
+    };
+    use crate::{DijkstrasAlgorithm, SearchConfig};
+
+    #[test]
+    fn ch_matches_dijkstra() {
+        for road_network in [grid_test_graph(12), oneway_grid_test_graph(12)] {
+            let mut dijkstra = DijkstrasAlgorithm::new(CompactGraph::from(&road_network));
+            let mut ch = ContractionHierarchy::from(&road_network);
+
+            assert!(ch.number_of_shortcuts() > 0);
+
+            //every pair of an unpacked path is an edge of the graph, or it would panic
+            let (settled_dijkstra, settled_ch) = assert_same_routes(&mut dijkstra, &mut ch, 144);
+
+            //the node ids of the grid are its node indices
+            for source in (0..144).step_by(13) {
+                let distances =
+                    DijkstrasAlgorithm::search_graph(&ch.graph, source, &SearchConfig::default())
+                        .unwrap()
+                        .distances;
+
+                for target in (0..144).step_by(11) {
+                    assert_eq!(
+                        ch.distance(source, target).unwrap(),
+                        distances[target as usize]
+                    );
+                }
+            }
+
+            assert!(settled_ch < settled_dijkstra);
+        }
+    }
+
+    #[test]
+    fn unpacks_shortcuts() {
+        //a path 1 -> 2 -> ... -> 6 with a detour 1 -> 7 -> 6 which is longer
+        let mut road_network = RoadNetwork::new();
+
+        for tail in 1..6 {
+            road_network.insert_edge(tail, tail + 1, 2);
+        }
+        road_network.insert_edge(1, 7, 6);
+        road_network.insert_edge(7, 6, 6);
+
+        let ch = ContractionHierarchy::from(&road_network);
+        assert!(ch.number_of_shortcuts() > 0);
+
+        let path = ch.search(0, 5).unwrap().path.unwrap();
+        assert_eq!(path.path, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(path.edge_costs, vec![2; 5]);
+
+        assert!(ch.search(5, 0).unwrap().path.is_none());
+        assert_eq!(ch.distance(5, 0).unwrap(), BastPriorityValue::Infinity);
+        assert!(matches!(
+            ch.distance(0, 7),
+            Err(RoutingError::NodeIndexOutOfRange(7))
+        ));
+        assert!(matches!(
+            ch.search(7, 0),
+            Err(RoutingError::NodeIndexOutOfRange(7))
+        ));
+
+        let mut ch = ContractionHierarchy::from(&small_test_graph());
+        assert_eq!(ch.search(1, 1).unwrap().path.unwrap().path, vec![2]);
+        assert!(matches!(
+            ch.route(&RouteQuery {
+                source: 1,
+                target: 4
+            }),
+            Err(RoutingError::UnreachableTarget {
+                source: 1,
+                target: 4
+            })
+        ));
+    }
+}
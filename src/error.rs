@@ -0,0 +1,233 @@
+//! The error type of the crate.
+
+use std::fmt;
+
+/// Everything that can go wrong while importing a graph or answering a query.
+#[derive(Debug)]
+pub enum RoutingError {
+    /// The file could not be opened or read.
+    Io(std::io::Error),
+    /// A block of the `.osm.pbf` file could not be decoded.
+    MalformedPbf(osmpbfreader::Error),
+    /// The OSM node id is not part of the graph.
+    UnknownNode(i64),
+    /// The node index is not part of the graph.
+    NodeIndexOutOfRange(u32),
+    /// Data computed for every node, e.g. landmark distances, was computed on another graph.
+    NodeCountMismatch {
+        /// Nodes of the graph.
+        expected: usize,
+        /// Entries of the data.
+        found: usize,
+    },
+    /// The lower bounds were computed for another target than the one of the query.
+    TargetMismatch {
+        /// Node index the bounds lead to.
+        expected: u32,
+        /// Node index the query is for.
+        found: u32,
+    },
+    /// The graph has no edges.
+    EmptyGraph,
+    /// There is no path from the source to the target.
+    UnreachableTarget {
+        /// OSM node id the route starts at.
+        source: i64,
+        /// OSM node id the route cannot reach.
+        target: i64,
+    },
+    /// The file is not one of `kind` this version can read.
+    InvalidFile {
+        /// What the file was supposed to hold.
+        kind: FileKind,
+        /// What is wrong with it.
+        reason: &'static str,
+    },
+    /// The data in the file was computed on a different graph, identified by its checksum.
+    GraphMismatch {
+        /// What the file holds.
+        kind: FileKind,
+        /// Checksum of the graph given to the loader.
+        expected: u64,
+        /// Checksum stored in the file.
+        found: u64,
+    },
+    /// The regions given for arc flags do not fit the graph.
+    InvalidPartition(&'static str),
+    /// The region is not one of the regions of the arc flags.
+    RegionOutOfRange(u8),
+}
+
+impl fmt::Display for RoutingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RoutingError::Io(error) => write!(f, "could not read the file: {}", error),
+            RoutingError::MalformedPbf(error) => write!(f, "malformed pbf file: {}", error),
+            RoutingError::UnknownNode(node) => write!(f, "node {} is not in the graph", node),
+            RoutingError::NodeIndexOutOfRange(node) => {
+                write!(f, "node index {} is out of range", node)
+            }
+            RoutingError::NodeCountMismatch { expected, found } => write!(
+                f,
+                "data for {} nodes does not fit a graph of {} nodes",
+                found, expected
+            ),
+            RoutingError::TargetMismatch { expected, found } => write!(
+                f,
+                "the bounds towards node index {} cannot guide a query to node index {}",
+                expected, found
+            ),
+            RoutingError::EmptyGraph => write!(f, "the graph has no edges"),
+            RoutingError::UnreachableTarget { source, target } => {
+                write!(f, "node {} cannot be reached from node {}", target, source)
+            }
+            RoutingError::InvalidFile { kind, reason } => {
+                write!(f, "invalid {} file: {}", kind, reason)
+            }
+            RoutingError::GraphMismatch {
+                kind,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} file belongs to graph {:016x}, not to graph {:016x}",
+                kind, found, expected
+            ),
+            RoutingError::InvalidPartition(reason) => write!(f, "invalid partition: {}", reason),
+            RoutingError::RegionOutOfRange(region) => {
+                write!(f, "region {} is out of range", region)
+            }
+        }
+    }
+}
+
+/// What a saved file holds, see [`RoutingError::InvalidFile`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    /// Written by [`crate::LandmarkDatabase::save`].
+    LandmarkDatabase,
+    /// Written by [`crate::HubLabels::save`].
+    HubLabels,
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileKind::LandmarkDatabase => write!(f, "landmark database"),
+            FileKind::HubLabels => write!(f, "hub label"),
+        }
+    }
+}
+
+impl std::error::Error for RoutingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RoutingError::Io(error) => Some(error),
+            RoutingError::MalformedPbf(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for RoutingError {
+    fn from(error: std::io::Error) -> RoutingError {
+        RoutingError::Io(error)
+    }
+}
+
+impl From<osmpbfreader::Error> for RoutingError {
+    fn from(error: osmpbfreader::Error) -> RoutingError {
+        match error {
+            osmpbfreader::Error::Io(error) => RoutingError::Io(error),
+            error => RoutingError::MalformedPbf(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::small_test_graph;
+    use crate::{
+        precompute_landmark_distances, transform_landmark_db_into_heuristic, CarProfile,
+        CompactGraph, DijkstrasAlgorithm, LandmarkPotential, LandmarkSelection, Metric, Potential,
+        RoadNetwork,
+    };
+    use std::sync::Arc;
+
+    fn read(contents: Option<&[u8]>) -> Result<RoadNetwork, RoutingError> {
+        let path = std::env::temp_dir().join(format!(
+            "bast-routing-error-{}-{}.osm.pbf",
+            std::process::id(),
+            contents.map_or(0, |contents| contents.len())
+        ));
+
+        if let Some(contents) = contents {
+            std::fs::write(&path, contents).unwrap();
+        }
+
+        let result = RoadNetwork::read_from_osm_file(
+            path.to_str().unwrap(),
+            &CarProfile,
+            &Metric::TravelTime,
+        );
+
+        let _ = std::fs::remove_file(&path);
+
+        result
+    }
+
+    #[test]
+    fn import_errors() {
+        assert!(matches!(read(None), Err(RoutingError::Io(_))));
+        assert!(matches!(read(Some(b"")), Err(RoutingError::EmptyGraph)));
+
+        //a blob header length followed by bytes which are not a blob header
+        let garbage = [0, 0, 0, 8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert!(matches!(
+            read(Some(&garbage)),
+            Err(RoutingError::MalformedPbf(_))
+        ));
+    }
+
+    #[test]
+    fn heuristic_errors() {
+        let graph = CompactGraph::from(&small_test_graph());
+        let landmark_database =
+            precompute_landmark_distances(&graph, 2, LandmarkSelection::default());
+
+        assert!(transform_landmark_db_into_heuristic(&graph, &landmark_database, 3).is_ok());
+        assert!(matches!(
+            transform_landmark_db_into_heuristic(&graph, &landmark_database, 4),
+            Err(RoutingError::NodeIndexOutOfRange(4))
+        ));
+
+        //distances computed on a smaller graph
+        let mut routing = DijkstrasAlgorithm::new(graph.clone());
+        routing.reduce_to_largest_connected_component().unwrap();
+        let smaller_database =
+            precompute_landmark_distances(&routing.graph, 2, LandmarkSelection::default());
+        assert!(matches!(
+            transform_landmark_db_into_heuristic(&graph, &smaller_database, 0),
+            Err(RoutingError::NodeCountMismatch {
+                expected: 4,
+                found: 3
+            })
+        ));
+
+        let mut potential = LandmarkPotential::new(&routing.graph, Arc::new(smaller_database));
+        assert!(matches!(
+            potential.set_query(&graph, 0, 1),
+            Err(RoutingError::NodeCountMismatch {
+                expected: 4,
+                found: 3
+            })
+        ));
+
+        let mut empty = DijkstrasAlgorithm::new(CompactGraph::default());
+        assert!(matches!(
+            empty.find_largest_connected_component(),
+            Err(RoutingError::EmptyGraph)
+        ));
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::Write;
+
+use crate::{CompactGraph, FileKind, RoutingError};
+
+//every saved file starts with this header, all integers little endian:
+//magic, format version u32, checksum of the graph the data was computed on u64, number of nodes u64
+const HEADER_LENGTH: usize = 8 + 4 + 8 + 8;
+
+/// How one kind of file is recognised, the rest of its layout is up to the kind.
+pub(crate) struct FileFormat {
+    pub kind: FileKind,
+    pub magic: &'static [u8; 8],
+    pub version: u32,
+}
+
+impl FileFormat {
+    pub(crate) fn write_header(
+        &self,
+        writer: &mut impl Write,
+        graph: &CompactGraph,
+    ) -> Result<(), RoutingError> {
+        writer.write_all(self.magic)?;
+        writer.write_all(&self.version.to_le_bytes())?;
+        writer.write_all(&graph.checksum().to_le_bytes())?;
+        writer.write_all(&(graph.number_of_nodes() as u64).to_le_bytes())?;
+
+        Ok(())
+    }
+
+    /// Checks the header against `graph` and returns what follows it.
+    pub(crate) fn read_header<'a>(
+        &self,
+        file: &'a [u8],
+        graph: &CompactGraph,
+    ) -> Result<FileReader<'a>, RoutingError> {
+        if file.len() < HEADER_LENGTH {
+            return Err(self.kind.invalid("file is truncated"));
+        }
+
+        let mut reader = FileReader {
+            kind: self.kind,
+            bytes: file,
+        };
+
+        if reader.take(self.magic.len())? != self.magic {
+            return Err(self.kind.invalid("unknown file type"));
+        }
+
+        if reader.take_u32()? != self.version {
+            return Err(self.kind.invalid("unsupported format version"));
+        }
+
+        let checksum = reader.take_u64()?;
+
+        if checksum != graph.checksum() {
+            return Err(RoutingError::GraphMismatch {
+                kind: self.kind,
+                expected: graph.checksum(),
+                found: checksum,
+            });
+        }
+
+        if reader.take_u64()? != graph.number_of_nodes() as u64 {
+            return Err(self.kind.invalid("wrong number of nodes"));
+        }
+
+        Ok(reader)
+    }
+}
+
+impl FileKind {
+    pub(crate) fn invalid(self, reason: &'static str) -> RoutingError {
+        RoutingError::InvalidFile { kind: self, reason }
+    }
+}
+
+/// The part of a file which was not read yet.
+pub(crate) struct FileReader<'a> {
+    kind: FileKind,
+    bytes: &'a [u8],
+}
+
+impl<'a> FileReader<'a> {
+    pub(crate) fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Splits off the next `length` bytes.
+    pub(crate) fn take(&mut self, length: usize) -> Result<&'a [u8], RoutingError> {
+        if self.bytes.len() < length {
+            return Err(self.kind.invalid("file is truncated"));
+        }
+
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    pub(crate) fn take_u32(&mut self) -> Result<u32, RoutingError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn take_u64(&mut self) -> Result<u64, RoutingError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
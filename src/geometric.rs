@@ -0,0 +1,158 @@
+//! A* with straight line distances.
+
+use crate::compact_graph::CompactGraph;
+use crate::error::RoutingError;
+use crate::metric::CostUnit;
+use crate::potential::Potential;
+use crate::BastPriorityValue;
+
+/// Lower bound from the great-circle distance to the target, covered at the fastest speed of the graph.
+///
+/// Needs no preprocessing, only a coordinate for every node.
+/// It is 0 everywhere if the graph has none or its weights are [`CostUnit::Custom`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GeometricPotential {
+    //None until set_target() was called
+    target: Option<u32>,
+    //lowest cost of one metre of straight line, 0 if nothing is known
+    cost_per_metre: f64,
+}
+
+impl GeometricPotential {
+    /// The bound is 0 everywhere if `graph` has no coordinates or no known top speed.
+    pub fn new(graph: &CompactGraph) -> GeometricPotential {
+        let cost_per_metre = match graph.unit {
+            _ if graph.coordinates.len() != graph.number_of_nodes() => 0.0,
+            CostUnit::Metres => 1.0,
+            //one metre at v km/h takes 3.6 / v seconds
+            CostUnit::Milliseconds if graph.max_speed_kmh > 0.0 => {
+                3600.0 / graph.max_speed_kmh as f64
+            }
+            _ => 0.0,
+        };
+
+        GeometricPotential {
+            target: None,
+            cost_per_metre,
+        }
+    }
+
+    /// Bounds the distance to the node index `target` from now on.
+    pub fn set_target(&mut self, graph: &CompactGraph, target: u32) -> Result<(), RoutingError> {
+        if target as usize >= graph.number_of_nodes() {
+            return Err(RoutingError::NodeIndexOutOfRange(target));
+        }
+
+        self.target = Some(target);
+
+        Ok(())
+    }
+
+    //0 before a target is set
+    fn bound(&self, graph: &CompactGraph, node: u32) -> BastPriorityValue {
+        let (Some(target), true) = (self.target, self.cost_per_metre > 0.0) else {
+            return BastPriorityValue::Some(0);
+        };
+
+        let metres = graph.coordinates[node as usize]
+            .haversine_distance_to(&graph.coordinates[target as usize])
+            .meters();
+
+        //rounded down, the edge costs were rounded up from the same distances
+        BastPriorityValue::Some((metres * self.cost_per_metre).floor() as u32)
+    }
+}
+
+impl Potential for GeometricPotential {
+    fn set_query(&mut self, graph: &CompactGraph, _: u32, target: u32) -> Result<(), RoutingError> {
+        self.set_target(graph, target)
+    }
+
+    fn estimate(&mut self, graph: &CompactGraph, node: u32) -> BastPriorityValue {
+        self.bound(graph, node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{assert_same_routes, coordinate_grid_test_graph};
+    use crate::{DijkstrasAlgorithm, GeometricRouter, SearchConfig};
+
+    //n x n grid 100 m apart, every edge is driven at 20, 40 or 60 km/h, in milliseconds
+    fn timed_grid(n: i64) -> CompactGraph {
+        let mut graph = coordinate_grid_test_graph(n);
+
+        for (tail, heads) in graph.edges.iter_mut() {
+            for (head, cost) in heads.iter_mut() {
+                //the speed belongs to the edge, not to its direction
+                let node = *tail.min(head);
+                let speed_kmh = 20.0 * ((node / n + 2 * (node % n)) % 3 + 1) as f64;
+
+                let metres = graph.coordinates[tail]
+                    .haversine_distance_to(&graph.coordinates[head])
+                    .meters();
+                *cost = (metres / (speed_kmh / 3.6) * 1000.0).ceil() as u32;
+            }
+        }
+
+        graph.max_speed_kmh = 60.0;
+
+        CompactGraph::from(&graph)
+    }
+
+    #[test]
+    fn bounds_are_admissible() {
+        let graph = timed_grid(10);
+        let mut potential = GeometricPotential::new(&graph);
+
+        assert_eq!(potential.estimate(&graph, 5), BastPriorityValue::Some(0));
+
+        for target in [0, 45, 99] {
+            potential.set_target(&graph, target).unwrap();
+
+            let distances = DijkstrasAlgorithm::search_graph(
+                &graph.reversed(),
+                target,
+                &SearchConfig::default(),
+            )
+            .unwrap()
+            .distances;
+
+            for node in 0..100 {
+                assert!(potential.estimate(&graph, node) <= distances[node as usize]);
+            }
+
+            assert_eq!(
+                potential.estimate(&graph, target),
+                BastPriorityValue::Some(0)
+            );
+        }
+
+        assert!(potential.estimate(&graph, 0) > BastPriorityValue::Some(0));
+        assert!(matches!(
+            potential.set_target(&graph, 100),
+            Err(RoutingError::NodeIndexOutOfRange(100))
+        ));
+
+        //nothing is known about custom weights
+        let mut custom = graph.clone();
+        custom.unit = CostUnit::Custom;
+        let mut potential = GeometricPotential::new(&custom);
+        potential.set_target(&custom, 99).unwrap();
+        assert_eq!(potential.estimate(&custom, 0), BastPriorityValue::Some(0));
+    }
+
+    #[test]
+    fn geometric_router_matches_dijkstra() {
+        let graph = timed_grid(10);
+
+        let mut dijkstra = DijkstrasAlgorithm::new(graph.clone());
+        let mut geometric = GeometricRouter::new(graph);
+
+        let (settled_dijkstra, settled_geometric) =
+            assert_same_routes(&mut dijkstra, &mut geometric, 100);
+
+        assert!(settled_geometric < settled_dijkstra);
+    }
+}
@@ -0,0 +1,361 @@
+//! Hub labels, distance queries by merging two sorted label lists.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::file_format::FileFormat;
+use crate::{BastPriorityValue, CompactGraph, ContractionHierarchy, FileKind, RoutingError};
+
+/// Hub labels (Abraham et al., "A Hub-Based Labeling Algorithm for Shortest Paths on Road Networks")
+/// taken from the search spaces of a [`ContractionHierarchy`].
+///
+/// Every node keeps the hubs its upward searches reach with their distances. The distance between
+/// two nodes is the best sum over the hubs their labels share. Entries another hub already beats are pruned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HubLabels {
+    //hubs reached from each node
+    forward: Labels,
+    //hubs which reach each node
+    backward: Labels,
+}
+
+//the labels of all nodes back to back, (hub, distance) sorted by hub
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Labels {
+    first_entry: Vec<u32>,
+    entries: Vec<(u32, u32)>,
+}
+
+impl Labels {
+    fn from_lists(lists: Vec<Vec<(u32, u32)>>) -> Labels {
+        let mut labels = Labels {
+            first_entry: vec![0],
+            ..Labels::default()
+        };
+
+        for list in lists {
+            labels.entries.extend(list);
+            labels.first_entry.push(labels.entries.len() as u32);
+        }
+
+        labels
+    }
+
+    fn label(&self, node: u32) -> &[(u32, u32)] {
+        &self.entries
+            [self.first_entry[node as usize] as usize..self.first_entry[node as usize + 1] as usize]
+    }
+}
+
+//file layout of saved HubLabels after the header, all integers little endian:
+//number of forward entries u64, number of backward entries u64,
+//then for the forward and the backward labels: first entry of each node and one past the last as u32,
+//each entry as hub u32 and distance u32
+const HUB_LABEL_FILE: FileFormat = FileFormat {
+    kind: FileKind::HubLabels,
+    magic: b"BASTHUBL",
+    version: 1,
+};
+
+//best sum of distances over the hubs both labels share, both sorted by hub
+fn intersect(forward: &[(u32, u32)], backward: &[(u32, u32)]) -> BastPriorityValue {
+    let mut best = BastPriorityValue::Infinity;
+    let (mut i, mut j) = (0, 0);
+
+    while i < forward.len() && j < backward.len() {
+        match forward[i].0.cmp(&backward[j].0) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                best = best.min(BastPriorityValue::Some(forward[i].1 + backward[j].1));
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    best
+}
+
+//the label of node from its search space, without the entries a shorter path through another hub beats.
+//the labels of all hubs of higher rank on the other side are final already
+fn pruned_label(
+    node: u32,
+    mut search_space: Vec<(u32, u32)>,
+    other_side: &[Vec<(u32, u32)>],
+    forward: bool,
+) -> Vec<(u32, u32)> {
+    search_space.sort_unstable();
+
+    search_space
+        .iter()
+        .filter(|(hub, distance)| {
+            if *hub == node {
+                return true;
+            }
+
+            let hub_label = &other_side[*hub as usize];
+
+            let through_other_hubs = if forward {
+                intersect(&search_space, hub_label)
+            } else {
+                intersect(hub_label, &search_space)
+            };
+
+            through_other_hubs >= BastPriorityValue::Some(*distance)
+        })
+        .copied()
+        .collect()
+}
+
+impl HubLabels {
+    /// Labels every node of the graph `hierarchy` was built from.
+    pub fn new(hierarchy: &ContractionHierarchy) -> HubLabels {
+        let number_of_nodes = hierarchy.graph.number_of_nodes();
+
+        //most important first, so the labels of the hubs are done before they are used for pruning
+        let mut order: Vec<u32> = (0..number_of_nodes as u32).collect();
+        order.sort_unstable_by_key(|node| std::cmp::Reverse(hierarchy.rank(*node)));
+
+        let mut forward = vec![vec![]; number_of_nodes];
+        let mut backward = vec![vec![]; number_of_nodes];
+
+        for node in order {
+            forward[node as usize] =
+                pruned_label(node, hierarchy.search_space(node, true), &backward, true);
+            backward[node as usize] =
+                pruned_label(node, hierarchy.search_space(node, false), &forward, false);
+        }
+
+        HubLabels {
+            forward: Labels::from_lists(forward),
+            backward: Labels::from_lists(backward),
+        }
+    }
+
+    /// Nodes of the graph the labels were computed on.
+    pub fn number_of_nodes(&self) -> usize {
+        self.forward.first_entry.len() - 1
+    }
+
+    /// Entries of all forward and backward labels together.
+    pub fn number_of_entries(&self) -> usize {
+        self.forward.entries.len() + self.backward.entries.len()
+    }
+
+    /// Cost of the shortest path between the node indices `source` and `target`.
+    pub fn distance(&self, source: u32, target: u32) -> Result<BastPriorityValue, RoutingError> {
+        for node in [source, target] {
+            if node as usize >= self.number_of_nodes() {
+                return Err(RoutingError::NodeIndexOutOfRange(node));
+            }
+        }
+
+        Ok(intersect(
+            self.forward.label(source),
+            self.backward.label(target),
+        ))
+    }
+
+    /// Writes the labels to `path` in a versioned binary format,
+    /// together with the checksum of `graph`, the graph they were computed on.
+    pub fn save(&self, path: &str, graph: &CompactGraph) -> Result<(), RoutingError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        HUB_LABEL_FILE.write_header(&mut writer, graph)?;
+        writer.write_all(&(self.forward.entries.len() as u64).to_le_bytes())?;
+        writer.write_all(&(self.backward.entries.len() as u64).to_le_bytes())?;
+
+        for labels in [&self.forward, &self.backward] {
+            for first_entry in labels.first_entry.iter() {
+                writer.write_all(&first_entry.to_le_bytes())?;
+            }
+
+            for (hub, distance) in labels.entries.iter() {
+                writer.write_all(&hub.to_le_bytes())?;
+                writer.write_all(&distance.to_le_bytes())?;
+            }
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads labels written by [`HubLabels::save`],
+    /// fails if they were computed on another graph than `graph`.
+    pub fn load(path: &str, graph: &CompactGraph) -> Result<HubLabels, RoutingError> {
+        let file = std::fs::read(path)?;
+        let mut reader = HUB_LABEL_FILE.read_header(&file, graph)?;
+
+        let number_of_nodes = graph.number_of_nodes() as u64;
+        let entries = [reader.take_u64()?, reader.take_u64()?];
+
+        //checked before allocating anything, a corrupt count could be huge
+        let expected_length = entries[0]
+            .checked_add(entries[1])
+            .and_then(|entries| entries.checked_add(number_of_nodes + 1))
+            .and_then(|values| values.checked_mul(2 * 4));
+
+        if expected_length != Some(reader.remaining() as u64) {
+            return Err(FileKind::HubLabels.invalid("wrong file length"));
+        }
+
+        let mut read_labels = |entries: u64| -> Result<Labels, RoutingError> {
+            let labels = Labels {
+                first_entry: (0..=number_of_nodes)
+                    .map(|_| reader.take_u32())
+                    .collect::<Result<_, _>>()?,
+                entries: (0..entries)
+                    .map(|_| Ok((reader.take_u32()?, reader.take_u32()?)))
+                    .collect::<Result<_, RoutingError>>()?,
+            };
+
+            let ordered = labels.first_entry.first() == Some(&0)
+                && labels.first_entry.last() == Some(&(entries as u32))
+                && labels.first_entry.windows(2).all(|pair| pair[0] <= pair[1]);
+
+            //intersect() relies on every label being sorted by hub without repeats
+            if !ordered
+                || labels
+                    .entries
+                    .iter()
+                    .any(|(hub, _)| *hub as u64 >= number_of_nodes)
+                || (0..number_of_nodes as u32).any(|node| {
+                    labels
+                        .label(node)
+                        .windows(2)
+                        .any(|pair| pair[0].0 >= pair[1].0)
+                })
+            {
+                return Err(FileKind::HubLabels.invalid("labels are corrupt"));
+            }
+
+            Ok(labels)
+        };
+
+        Ok(HubLabels {
+            forward: read_labels(entries[0])?,
+            backward: read_labels(entries[1])?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{grid_test_graph, oneway_grid_test_graph};
+    use crate::{DijkstrasAlgorithm, SearchConfig};
+
+    #[test]
+    fn labels_match_dijkstra() {
+        for road_network in [grid_test_graph(10), oneway_grid_test_graph(10)] {
+            let hierarchy = ContractionHierarchy::from(&road_network);
+            let labels = HubLabels::new(&hierarchy);
+            let graph = &hierarchy.graph;
+
+            for source in 0..100 {
+                let distances =
+                    DijkstrasAlgorithm::search_graph(graph, source, &SearchConfig::default())
+                        .unwrap()
+                        .distances;
+
+                for target in 0..100 {
+                    assert_eq!(
+                        labels.distance(source, target).unwrap(),
+                        distances[target as usize]
+                    );
+                }
+            }
+
+            //without pruning every node would keep its whole search space
+            let unpruned: usize = (0..100)
+                .map(|node| {
+                    hierarchy.search_space(node, true).len()
+                        + hierarchy.search_space(node, false).len()
+                })
+                .sum();
+            assert!(labels.number_of_entries() < unpruned);
+
+            assert!(matches!(
+                labels.distance(0, 100),
+                Err(RoutingError::NodeIndexOutOfRange(100))
+            ));
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let hierarchy = ContractionHierarchy::from(&oneway_grid_test_graph(8));
+        let labels = HubLabels::new(&hierarchy);
+
+        let path = std::env::temp_dir().join(format!("bast-hub-labels-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        labels.save(path, &hierarchy.graph).unwrap();
+        assert_eq!(HubLabels::load(path, &hierarchy.graph).unwrap(), labels);
+
+        let other_graph = CompactGraph::from(&grid_test_graph(8));
+        assert!(matches!(
+            HubLabels::load(path, &other_graph),
+            Err(RoutingError::GraphMismatch {
+                kind: FileKind::HubLabels,
+                ..
+            })
+        ));
+
+        //two hubs of a forward label swapped, the label is no longer sorted
+        let mut bytes = std::fs::read(path).unwrap();
+        let node = (0..labels.number_of_nodes() as u32)
+            .find(|node| labels.forward.label(*node).len() >= 2)
+            .unwrap();
+        let first = labels.forward.first_entry[node as usize] as usize;
+        let hub = |entry: usize| 28 + 16 + 4 * (labels.number_of_nodes() + 1) + 8 * entry;
+        let mut flipped = bytes.clone();
+        flipped[hub(first)..hub(first) + 4]
+            .copy_from_slice(&bytes[hub(first + 1)..hub(first + 1) + 4]);
+        flipped[hub(first + 1)..hub(first + 1) + 4]
+            .copy_from_slice(&bytes[hub(first)..hub(first) + 4]);
+        std::fs::write(path, &flipped).unwrap();
+        assert!(matches!(
+            HubLabels::load(path, &hierarchy.graph),
+            Err(RoutingError::InvalidFile {
+                kind: FileKind::HubLabels,
+                reason: "labels are corrupt"
+            })
+        ));
+
+        bytes.truncate(bytes.len() - 4);
+        std::fs::write(path, &bytes).unwrap();
+        assert!(matches!(
+            HubLabels::load(path, &hierarchy.graph),
+            Err(RoutingError::InvalidFile {
+                kind: FileKind::HubLabels,
+                reason: "wrong file length"
+            })
+        ));
+
+        //a number of entries whose file length overflows
+        let mut corrupt_count = std::fs::read(path).unwrap();
+        corrupt_count[28..36].copy_from_slice(&(u64::MAX / 4).to_le_bytes());
+        std::fs::write(path, &corrupt_count).unwrap();
+        assert!(matches!(
+            HubLabels::load(path, &hierarchy.graph),
+            Err(RoutingError::InvalidFile {
+                kind: FileKind::HubLabels,
+                reason: "wrong file length"
+            })
+        ));
+
+        std::fs::write(path, b"BASTLMDB").unwrap();
+        assert!(matches!(
+            HubLabels::load(path, &hierarchy.graph),
+            Err(RoutingError::InvalidFile {
+                kind: FileKind::HubLabels,
+                reason: "file is truncated"
+            })
+        ));
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,1315 @@
+//! ALT: A* with landmarks and the triangle inequality.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+
+use crate::compact_graph::CompactGraph;
+use crate::error::{FileKind, RoutingError};
+use crate::file_format::FileFormat;
+use crate::parallel::{number_of_threads, parallel_fold};
+use crate::potential::{Potential, ZeroPotential};
+use crate::{run_search, BastPriorityValue, DijkstrasAlgorithm, SearchConfig};
+
+/// Distances from each landmark to every node and from every node to each landmark.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LandmarkDatabase {
+    /// Node index of each landmark.
+    pub landmarks: Vec<u32>,
+    /// `distances[i][node]` is the distance from `landmarks[i]` to `node`.
+    pub distances: Vec<Vec<BastPriorityValue>>,
+    /// `distances_to[i][node]` is the distance from `node` to `landmarks[i]`, computed on the reversed graph.
+    pub distances_to: Vec<Vec<BastPriorityValue>>,
+}
+
+//file layout of a saved LandmarkDatabase after the header, all integers little endian:
+//number of landmarks u64, the landmark node indices as u32,
+//then for each landmark its distances from and to every node as u32
+const LANDMARK_FILE: FileFormat = FileFormat {
+    kind: FileKind::LandmarkDatabase,
+    magic: b"BASTLMDB",
+    version: 1,
+};
+//stored for BastPriorityValue::Infinity
+const UNREACHABLE: u32 = u32::MAX;
+
+impl LandmarkDatabase {
+    /// Landmarks with distances in both directions, the bounds use `0..number_of_landmarks()`.
+    pub fn number_of_landmarks(&self) -> usize {
+        self.distances.len().min(self.distances_to.len())
+    }
+
+    /// Writes the distances to `path` in a versioned binary format,
+    /// together with the checksum of `graph`, the graph they were computed on.
+    /// Fails without writing if the database does not hold both distances for every landmark and node.
+    pub fn save(&self, path: &str, graph: &CompactGraph) -> Result<(), RoutingError> {
+        //load reads one distance table per direction and landmark, each with one entry per node
+        if self.distances.len() != self.landmarks.len()
+            || self.distances_to.len() != self.landmarks.len()
+        {
+            return Err(FileKind::LandmarkDatabase.invalid("not one distance table per landmark"));
+        }
+
+        if let Some(arr) = self
+            .distances
+            .iter()
+            .chain(self.distances_to.iter())
+            .find(|arr| arr.len() != graph.number_of_nodes())
+        {
+            return Err(RoutingError::NodeCountMismatch {
+                expected: graph.number_of_nodes(),
+                found: arr.len(),
+            });
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        LANDMARK_FILE.write_header(&mut writer, graph)?;
+        writer.write_all(&(self.landmarks.len() as u64).to_le_bytes())?;
+
+        for landmark in self.landmarks.iter() {
+            writer.write_all(&landmark.to_le_bytes())?;
+        }
+
+        for (from_landmark, to_landmark) in self.distances.iter().zip(self.distances_to.iter()) {
+            for distance in from_landmark.iter().chain(to_landmark.iter()) {
+                let value = match distance {
+                    BastPriorityValue::Some(value) => *value,
+                    BastPriorityValue::Infinity => UNREACHABLE,
+                };
+
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads distances written by [`LandmarkDatabase::save`],
+    /// fails if they were computed on another graph than `graph`.
+    pub fn load(path: &str, graph: &CompactGraph) -> Result<LandmarkDatabase, RoutingError> {
+        let file = std::fs::read(path)?;
+        let mut reader = LANDMARK_FILE.read_header(&file, graph)?;
+
+        let number_of_nodes = graph.number_of_nodes();
+        let number_of_landmarks = reader.take_u64()? as usize;
+
+        //checked before allocating anything, a corrupt count could be huge
+        let expected_length = (number_of_nodes as u64)
+            .checked_mul(2 * 4)
+            .and_then(|length| length.checked_add(4))
+            .and_then(|length| length.checked_mul(number_of_landmarks as u64));
+
+        if expected_length != Some(reader.remaining() as u64) {
+            return Err(FileKind::LandmarkDatabase.invalid("wrong file length"));
+        }
+
+        let landmarks = (0..number_of_landmarks)
+            .map(|_| reader.take_u32())
+            .collect::<Result<Vec<u32>, RoutingError>>()?;
+
+        if landmarks
+            .iter()
+            .any(|landmark| *landmark as usize >= number_of_nodes)
+        {
+            return Err(FileKind::LandmarkDatabase.invalid("landmark is not a node"));
+        }
+
+        let mut read_distances = || -> Result<Vec<BastPriorityValue>, RoutingError> {
+            Ok(reader
+                .take(4 * number_of_nodes)?
+                .chunks_exact(4)
+                .map(
+                    |chunk| match u32::from_le_bytes(chunk.try_into().unwrap()) {
+                        UNREACHABLE => BastPriorityValue::Infinity,
+                        value => BastPriorityValue::Some(value),
+                    },
+                )
+                .collect())
+        };
+
+        let mut distances = Vec::with_capacity(number_of_landmarks);
+        let mut distances_to = Vec::with_capacity(number_of_landmarks);
+
+        for _ in 0..number_of_landmarks {
+            distances.push(read_distances()?);
+            distances_to.push(read_distances()?);
+        }
+
+        Ok(LandmarkDatabase {
+            landmarks,
+            distances,
+            distances_to,
+        })
+    }
+}
+
+/// How the landmarks are picked, see Goldberg and Harrelson, "Computing the Shortest Path: A* Search Meets Graph Theory".
+///
+/// The seeded strategies pick the same landmarks for the same seed and graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LandmarkSelection {
+    /// Uniformly at random.
+    Random {
+        /// Seed of the random number generator.
+        seed: u64,
+    },
+    /// Each landmark is the node farthest away from the landmarks picked before.
+    Farthest {
+        /// Seed of the random number generator.
+        seed: u64,
+    },
+    /// Leaves of shortest path trees in the regions where the landmarks picked before give poor bounds.
+    Avoid {
+        /// Seed of the random number generator.
+        seed: u64,
+    },
+    /// The farthest node from the centre of the graph in each of equal sectors around it.
+    /// Needs coordinates, falls back to `Farthest` without them.
+    Planar,
+    /// The landmarks covering the most arcs out of a larger set of `Avoid` candidates.
+    MaxCover {
+        /// Seed of the random number generator.
+        seed: u64,
+    },
+}
+
+impl Default for LandmarkSelection {
+    fn default() -> LandmarkSelection {
+        LandmarkSelection::Avoid { seed: 0 }
+    }
+}
+
+/// Picks `number_of_landmarks` distinct node indices, fewer if the graph is smaller.
+pub fn select_landmarks(
+    graph: &CompactGraph,
+    number_of_landmarks: usize,
+    selection: LandmarkSelection,
+) -> Vec<u32> {
+    select_landmarks_with_distances(graph, number_of_landmarks, selection).0
+}
+
+//the landmarks along with the distances from each of them, if the selection already searched from it
+fn select_landmarks_with_distances(
+    graph: &CompactGraph,
+    number_of_landmarks: usize,
+    selection: LandmarkSelection,
+) -> (Vec<u32>, Vec<Option<Vec<BastPriorityValue>>>) {
+    let number_of_landmarks = number_of_landmarks.min(graph.number_of_nodes());
+
+    if number_of_landmarks == 0 {
+        return (Vec::new(), Vec::new());
+    }
+
+    let without_distances = |landmarks: Vec<u32>| {
+        let distances = vec![None; landmarks.len()];
+        (landmarks, distances)
+    };
+
+    match selection {
+        LandmarkSelection::Random { seed } => without_distances(
+            rand::seq::index::sample(
+                &mut StdRng::seed_from_u64(seed),
+                graph.number_of_nodes(),
+                number_of_landmarks,
+            )
+            .into_iter()
+            .map(|node| node as u32)
+            .collect(),
+        ),
+        LandmarkSelection::Farthest { seed } => {
+            let (landmarks, distances) =
+                farthest_landmarks(graph, number_of_landmarks, &mut StdRng::seed_from_u64(seed));
+
+            (landmarks, distances.into_iter().map(Some).collect())
+        }
+        LandmarkSelection::Avoid { seed } => {
+            let mut landmarks = Vec::new();
+            let mut distances = Vec::new();
+
+            add_avoid_landmarks(
+                graph,
+                number_of_landmarks,
+                &mut StdRng::seed_from_u64(seed),
+                &mut landmarks,
+                &mut distances,
+            );
+
+            (landmarks, distances.into_iter().map(Some).collect())
+        }
+        LandmarkSelection::Planar => {
+            without_distances(planar_landmarks(graph, number_of_landmarks))
+        }
+        LandmarkSelection::MaxCover { seed } => without_distances(max_cover_landmarks(
+            graph,
+            number_of_landmarks,
+            &mut StdRng::seed_from_u64(seed),
+        )),
+    }
+}
+
+fn settle_all(graph: &CompactGraph, source: u32) -> Vec<BastPriorityValue> {
+    run_search(graph, &mut ZeroPotential, source, &SearchConfig::default()).distances
+}
+
+//the landmarks and the distances from each of them
+fn farthest_landmarks(
+    graph: &CompactGraph,
+    number_of_landmarks: usize,
+    rng: &mut StdRng,
+) -> (Vec<u32>, Vec<Vec<BastPriorityValue>>) {
+    let number_of_nodes = graph.number_of_nodes();
+
+    //the first landmark is the node farthest from a random start
+    let start = rng.gen_range(0..number_of_nodes) as u32;
+    let mut nearest_landmark = settle_all(graph, start);
+
+    let mut landmarks: Vec<u32> = Vec::with_capacity(number_of_landmarks);
+    let mut distances: Vec<Vec<BastPriorityValue>> = Vec::with_capacity(number_of_landmarks);
+
+    while landmarks.len() < number_of_landmarks {
+        //nodes no landmark reaches are infinitely far away and picked first
+        let next = (0..number_of_nodes as u32)
+            .filter(|node| !landmarks.contains(node))
+            .max_by_key(|node| nearest_landmark[*node as usize])
+            .unwrap();
+
+        let from_next = settle_all(graph, next);
+
+        if landmarks.is_empty() {
+            nearest_landmark = from_next.clone();
+        } else {
+            for (nearest, distance) in nearest_landmark.iter_mut().zip(from_next.iter()) {
+                *nearest = (*nearest).min(*distance);
+            }
+        }
+
+        landmarks.push(next);
+        distances.push(from_next);
+    }
+
+    (landmarks, distances)
+}
+
+//adds landmarks until there are `number_of_landmarks`, keeps `distances` in step with `landmarks`
+fn add_avoid_landmarks(
+    graph: &CompactGraph,
+    number_of_landmarks: usize,
+    rng: &mut StdRng,
+    landmarks: &mut Vec<u32>,
+    distances: &mut Vec<Vec<BastPriorityValue>>,
+) {
+    let number_of_nodes = graph.number_of_nodes();
+
+    while landmarks.len() < number_of_landmarks {
+        let root = rng.gen_range(0..number_of_nodes) as u32;
+        let tree = run_search(graph, &mut ZeroPotential, root, &SearchConfig::default());
+
+        //weight of a node is how much the current landmarks underestimate its distance from the root,
+        //size is the weight of its subtree in the shortest path tree, zero if the subtree has a landmark
+        let mut size = vec![0u64; number_of_nodes];
+        let mut has_landmark = vec![false; number_of_nodes];
+
+        for landmark in landmarks.iter() {
+            has_landmark[*landmark as usize] = true;
+        }
+
+        for node in tree.settled.iter().rev() {
+            let node = *node as usize;
+
+            let BastPriorityValue::Some(distance) = tree.distances[node] else {
+                continue;
+            };
+
+            let lower_bound = distances
+                .iter()
+                .map(|arr| match (arr[root as usize], arr[node]) {
+                    (BastPriorityValue::Some(lr), BastPriorityValue::Some(lv)) => {
+                        lv.saturating_sub(lr)
+                    }
+                    _ => 0,
+                })
+                .max()
+                .unwrap_or(0);
+
+            size[node] += (distance - lower_bound.min(distance)) as u64;
+
+            if let Some(parent) = tree.prev[node] {
+                size[parent as usize] += size[node];
+                has_landmark[parent as usize] |= has_landmark[node];
+            }
+        }
+
+        for node in tree.settled.iter() {
+            if has_landmark[*node as usize] {
+                size[*node as usize] = 0;
+            }
+        }
+
+        let mut best_child: Vec<Option<u32>> = vec![None; number_of_nodes];
+
+        for node in tree.settled.iter() {
+            if let Some(parent) = tree.prev[*node as usize] {
+                let parent = parent as usize;
+
+                if best_child[parent]
+                    .is_none_or(|child| size[*node as usize] > size[child as usize])
+                {
+                    best_child[parent] = Some(*node);
+                }
+            }
+        }
+
+        let heaviest = tree
+            .settled
+            .iter()
+            .copied()
+            .max_by_key(|node| size[*node as usize])
+            .filter(|node| size[*node as usize] > 0);
+
+        let next = match heaviest {
+            //walk down to a leaf along the heaviest children
+            Some(mut node) => {
+                while let Some(child) = best_child[node as usize] {
+                    node = child;
+                }
+                node
+            }
+            //the landmarks are exact for everything the root reaches, try anywhere else
+            None => loop {
+                let node = rng.gen_range(0..number_of_nodes) as u32;
+
+                if !landmarks.contains(&node) {
+                    break node;
+                }
+            },
+        };
+
+        distances.push(settle_all(graph, next));
+        landmarks.push(next);
+    }
+}
+
+fn planar_landmarks(graph: &CompactGraph, number_of_landmarks: usize) -> Vec<u32> {
+    let number_of_nodes = graph.number_of_nodes();
+
+    if graph.coordinates.len() != number_of_nodes {
+        return farthest_landmarks(graph, number_of_landmarks, &mut StdRng::seed_from_u64(0)).0;
+    }
+
+    let centre_latitude =
+        graph.coordinates.iter().map(|c| c.latitude()).sum::<f64>() / number_of_nodes as f64;
+    let centre_longitude =
+        graph.coordinates.iter().map(|c| c.longitude()).sum::<f64>() / number_of_nodes as f64;
+    let longitude_scale = centre_latitude.to_radians().cos();
+
+    //offset from the centre in roughly equal units north and east
+    let offset = |node: usize| {
+        let coordinate = graph.coordinates[node];
+        (
+            coordinate.latitude() - centre_latitude,
+            (coordinate.longitude() - centre_longitude) * longitude_scale,
+        )
+    };
+
+    let centre = (0..number_of_nodes)
+        .min_by(|a, b| {
+            let (a_north, a_east) = offset(*a);
+            let (b_north, b_east) = offset(*b);
+            (a_north.hypot(a_east)).total_cmp(&b_north.hypot(b_east))
+        })
+        .unwrap();
+
+    let sectors: Vec<usize> = (0..number_of_nodes)
+        .map(|node| {
+            let (north, east) = offset(node);
+            let turn = (north.atan2(east) + std::f64::consts::PI) / std::f64::consts::TAU;
+            ((turn * number_of_landmarks as f64) as usize).min(number_of_landmarks - 1)
+        })
+        .collect();
+
+    let from_centre = settle_all(graph, centre as u32);
+
+    //farthest node of each sector by road, empty sectors are filled with the farthest nodes left
+    let mut by_distance: Vec<u32> = (0..number_of_nodes as u32).collect();
+    by_distance.sort_by_key(|node| std::cmp::Reverse(from_centre[*node as usize]));
+
+    let mut landmarks: Vec<u32> = Vec::with_capacity(number_of_landmarks);
+    let mut sector_taken = vec![false; number_of_landmarks];
+
+    for node in by_distance.iter() {
+        let sector = sectors[*node as usize];
+
+        if !sector_taken[sector] {
+            sector_taken[sector] = true;
+            landmarks.push(*node);
+        }
+    }
+
+    for node in by_distance.iter() {
+        if landmarks.len() == number_of_landmarks {
+            break;
+        }
+
+        if !landmarks.contains(node) {
+            landmarks.push(*node);
+        }
+    }
+
+    landmarks
+}
+
+//arcs (v,w) on which the landmark bound is exact, d(L,v) + c(v,w) = d(L,w), as a bitset over the edge indices
+fn covered_arcs(graph: &CompactGraph, distances: &[BastPriorityValue]) -> Vec<u64> {
+    let mut covered = vec![0u64; graph.number_of_edges().div_ceil(64)];
+
+    for tail in 0..graph.number_of_nodes() as u32 {
+        let BastPriorityValue::Some(tail_distance) = distances[tail as usize] else {
+            continue;
+        };
+
+        for edge in graph.edge_range(tail) {
+            let head = graph.head[edge] as usize;
+
+            if distances[head] == BastPriorityValue::Some(tail_distance + graph.weight[edge]) {
+                covered[edge / 64] |= 1 << (edge % 64);
+            }
+        }
+    }
+
+    covered
+}
+
+fn max_cover_landmarks(
+    graph: &CompactGraph,
+    number_of_landmarks: usize,
+    rng: &mut StdRng,
+) -> Vec<u32> {
+    //candidates are collected from rounds of avoid, each round drops half of the landmarks at random
+    let mut landmarks = Vec::new();
+    let mut distances = Vec::new();
+    let mut candidates: Vec<u32> = Vec::new();
+    let mut coverage: Vec<Vec<u64>> = Vec::new();
+
+    for _ in 0..8 {
+        add_avoid_landmarks(
+            graph,
+            number_of_landmarks,
+            rng,
+            &mut landmarks,
+            &mut distances,
+        );
+
+        for (landmark, arr) in landmarks.iter().zip(distances.iter()) {
+            if !candidates.contains(landmark) {
+                candidates.push(*landmark);
+                coverage.push(covered_arcs(graph, arr));
+            }
+        }
+
+        if candidates.len() >= 4 * number_of_landmarks {
+            break;
+        }
+
+        for _ in 0..number_of_landmarks.div_ceil(2) {
+            let dropped = rng.gen_range(0..landmarks.len());
+            landmarks.swap_remove(dropped);
+            distances.swap_remove(dropped);
+        }
+    }
+
+    //greedy maximum coverage, always take the candidate covering the most arcs no one covers yet
+    let mut covered = vec![0u64; graph.number_of_edges().div_ceil(64)];
+    let mut picked = vec![false; candidates.len()];
+    let mut selected = Vec::with_capacity(number_of_landmarks);
+
+    while selected.len() < number_of_landmarks {
+        let best = (0..candidates.len())
+            .filter(|candidate| !picked[*candidate])
+            .max_by_key(|candidate| {
+                let newly_covered: u32 = coverage[*candidate]
+                    .iter()
+                    .zip(covered.iter())
+                    .map(|(arcs, already)| (arcs & !already).count_ones())
+                    .sum();
+                //prefer the earlier candidate on ties
+                (newly_covered, std::cmp::Reverse(*candidate))
+            })
+            .unwrap();
+
+        for (already, arcs) in covered.iter_mut().zip(coverage[best].iter()) {
+            *already |= arcs;
+        }
+
+        picked[best] = true;
+        selected.push(candidates[best]);
+    }
+
+    selected
+}
+
+/// Picks landmarks with `selection` and runs a full Dijkstra from each of them,
+/// once on the graph and once on the reversed graph, on all cores.
+///
+/// Searches the selection already ran from a landmark are not repeated.
+pub fn precompute_landmark_distances(
+    graph: &CompactGraph,
+    number_of_landmarks: usize,
+    selection: LandmarkSelection,
+) -> LandmarkDatabase {
+    let (landmarks, distances) =
+        select_landmarks_with_distances(graph, number_of_landmarks, selection);
+
+    complete_landmark_distances(graph, landmarks, distances, number_of_threads())
+}
+
+/// Distances from and to the given landmarks, the searches are spread over `number_of_threads` threads.
+///
+/// The result does not depend on the number of threads.
+pub fn compute_landmark_distances(
+    graph: &CompactGraph,
+    landmarks: Vec<u32>,
+    number_of_threads: usize,
+) -> LandmarkDatabase {
+    let distances = vec![None; landmarks.len()];
+
+    complete_landmark_distances(graph, landmarks, distances, number_of_threads)
+}
+
+//runs the searches from the landmarks whose distances are None and all searches to the landmarks
+fn complete_landmark_distances(
+    graph: &CompactGraph,
+    landmarks: Vec<u32>,
+    known_distances: Vec<Option<Vec<BastPriorityValue>>>,
+    number_of_threads: usize,
+) -> LandmarkDatabase {
+    let reversed_graph = graph.reversed();
+
+    //job i < landmarks.len() is the search from landmarks[i], job landmarks.len() + i the one to it
+    let jobs: Vec<usize> = (0..2 * landmarks.len())
+        .filter(|job| *job >= landmarks.len() || known_distances[*job].is_none())
+        .collect();
+    let results: Vec<(usize, Vec<BastPriorityValue>)> =
+        parallel_fold(jobs.len(), number_of_threads, Vec::new, |finished, next| {
+            let job = jobs[next];
+            let (searched_graph, landmark) = if job < landmarks.len() {
+                (graph, landmarks[job])
+            } else {
+                (&reversed_graph, landmarks[job - landmarks.len()])
+            };
+
+            let distances = run_search(
+                searched_graph,
+                &mut ZeroPotential,
+                landmark,
+                &SearchConfig::default(),
+            )
+            .distances;
+
+            finished.push((job, distances));
+        })
+        .into_iter()
+        .flatten()
+        .collect();
+
+    //whichever thread did a job, it ends up in its place
+    let mut by_job = known_distances;
+    by_job.resize(2 * landmarks.len(), None);
+
+    for (job, arr) in results {
+        by_job[job] = Some(arr);
+    }
+
+    let mut distances: Vec<Vec<BastPriorityValue>> =
+        by_job.into_iter().map(Option::unwrap).collect();
+    let distances_to = distances.split_off(landmarks.len());
+
+    LandmarkDatabase {
+        landmarks,
+        distances,
+        distances_to,
+    }
+}
+
+//the database has to cover every node and the target has to be one of them,
+//otherwise it was computed on another graph
+pub(crate) fn check_landmark_database(
+    number_of_nodes: usize,
+    landmark_database: &LandmarkDatabase,
+    target: u32,
+) -> Result<(), RoutingError> {
+    if target as usize >= number_of_nodes {
+        return Err(RoutingError::NodeIndexOutOfRange(target));
+    }
+
+    if let Some(arr) = landmark_database
+        .distances
+        .iter()
+        .chain(landmark_database.distances_to.iter())
+        .find(|arr| arr.len() != number_of_nodes)
+    {
+        return Err(RoutingError::NodeCountMismatch {
+            expected: number_of_nodes,
+            found: arr.len(),
+        });
+    }
+
+    Ok(())
+}
+
+//each landmark gives two lower bounds on d(u,t) by the triangle inequality:
+//d(L,t) <= d(L,u) + d(u,t), so d(L,t) - d(L,u) <= d(u,t)
+//d(u,L) <= d(u,t) + d(t,L), so d(u,L) - d(t,L) <= d(u,t)
+//edges are not symmetric, so |d(L,u) - d(L,t)| would not be a lower bound
+fn single_landmark_bound(
+    landmark_database: &LandmarkDatabase,
+    landmark: usize,
+    node: u32,
+    target: u32,
+) -> u32 {
+    let difference = |first: BastPriorityValue, second: BastPriorityValue| match (first, second) {
+        (BastPriorityValue::Some(first), BastPriorityValue::Some(second)) => {
+            first.saturating_sub(second)
+        }
+        //nothing is known if either is unreachable
+        _ => 0,
+    };
+
+    let from_landmark = &landmark_database.distances[landmark];
+    let to_landmark = &landmark_database.distances_to[landmark];
+    let node = node as usize;
+    let target = target as usize;
+
+    difference(from_landmark[target], from_landmark[node])
+        .max(difference(to_landmark[node], to_landmark[target]))
+}
+
+//the best bound of the given landmarks, indices into landmark_database.landmarks
+pub(crate) fn landmark_bound(
+    landmark_database: &LandmarkDatabase,
+    landmarks: impl Iterator<Item = usize>,
+    node: u32,
+    target: u32,
+) -> u32 {
+    landmarks
+        .map(|landmark| single_landmark_bound(landmark_database, landmark, node, target))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Lower bound on the distance from every node to `target`, built from the landmark distances.
+///
+/// This touches every node for every landmark, [`LandmarkPotential`] only does the work for the nodes a search reaches.
+pub fn transform_landmark_db_into_heuristic(
+    graph: &CompactGraph,
+    landmark_database: &LandmarkDatabase,
+    target: u32,
+) -> Result<Vec<BastPriorityValue>, RoutingError> {
+    check_landmark_database(graph.number_of_nodes(), landmark_database, target)?;
+
+    Ok((0..graph.number_of_nodes() as u32)
+        .map(|node| {
+            BastPriorityValue::Some(landmark_bound(
+                landmark_database,
+                0..landmark_database.number_of_landmarks(),
+                node,
+                target,
+            ))
+        })
+        .collect())
+}
+
+/// Landmark lower bounds towards one target, computed the first time the search asks for a node
+/// and remembered until the target changes.
+///
+/// It can restrict each query to the few landmarks which bound it best ("active" landmarks,
+/// see Goldberg and Werneck, "Computing Point-to-Point Shortest Paths from External Memory")
+/// and add more while the search runs if they bound the settled nodes better.
+#[derive(Clone, Debug)]
+pub struct LandmarkPotential {
+    landmark_database: Arc<LandmarkDatabase>,
+    number_of_nodes: usize,
+    //None until set_target() was called
+    target: Option<u32>,
+    //indices into landmark_database.landmarks which the bounds are taken from
+    active: Vec<usize>,
+    //how many landmarks set_query() starts with, None for all of them
+    active_limit: Option<usize>,
+    add_during_search: bool,
+    settled_in_query: usize,
+    //memo[node] is only valid if memo_marks[node] == round, so a new target does not have to clear it
+    memo: Vec<BastPriorityValue>,
+    memo_marks: Vec<usize>,
+    round: usize,
+}
+
+impl LandmarkPotential {
+    /// Bounds for `graph` from distances which were computed on it.
+    pub fn new(
+        graph: &CompactGraph,
+        landmark_database: Arc<LandmarkDatabase>,
+    ) -> LandmarkPotential {
+        LandmarkPotential {
+            landmark_database,
+            number_of_nodes: graph.number_of_nodes(),
+            target: None,
+            active: Vec::new(),
+            active_limit: None,
+            add_during_search: false,
+            settled_in_query: 0,
+            memo: vec![BastPriorityValue::Some(0); graph.number_of_nodes()],
+            memo_marks: vec![0; graph.number_of_nodes()],
+            round: 0,
+        }
+    }
+
+    /// Makes [`LandmarkPotential::set_query`] start with the `count` landmarks which give the best bound
+    /// from the source to the target, `add_during_search` lets the search activate more of them.
+    pub fn with_active_landmarks(
+        mut self,
+        count: usize,
+        add_during_search: bool,
+    ) -> LandmarkPotential {
+        self.set_active_landmarks(count, add_during_search);
+        self
+    }
+
+    /// Like [`LandmarkPotential::with_active_landmarks`], for a potential which is already in use.
+    pub fn set_active_landmarks(&mut self, count: usize, add_during_search: bool) {
+        self.active_limit = Some(count);
+        self.add_during_search = add_during_search;
+    }
+
+    fn number_of_landmarks(&self) -> usize {
+        self.landmark_database.number_of_landmarks()
+    }
+
+    /// Points the bounds at a new target using all landmarks, this forgets every remembered bound.
+    pub fn set_target(&mut self, target: u32) -> Result<(), RoutingError> {
+        check_landmark_database(self.number_of_nodes, &self.landmark_database, target)?;
+
+        self.target = Some(target);
+        self.active = (0..self.number_of_landmarks()).collect();
+        self.settled_in_query = 0;
+        self.round += 1;
+
+        Ok(())
+    }
+
+    /// Indices into [`LandmarkDatabase::landmarks`] the bounds currently come from.
+    pub fn active_landmarks(&self) -> &[usize] {
+        &self.active
+    }
+
+    /// The landmark distances the bounds are computed from.
+    pub fn landmark_database(&self) -> &LandmarkDatabase {
+        &self.landmark_database
+    }
+
+    //memoised bound of the active landmarks, 0 before a target is set
+    fn bound(&mut self, node: u32) -> BastPriorityValue {
+        let Some(target) = self.target else {
+            return BastPriorityValue::Some(0);
+        };
+
+        let node_index = node as usize;
+
+        if self.memo_marks[node_index] != self.round {
+            self.memo[node_index] = BastPriorityValue::Some(landmark_bound(
+                &self.landmark_database,
+                self.active.iter().copied(),
+                node,
+                target,
+            ));
+            self.memo_marks[node_index] = self.round;
+        }
+
+        self.memo[node_index]
+    }
+}
+
+impl Potential for LandmarkPotential {
+    /// Points the bounds at a new target and activates the landmarks which are best for this query.
+    fn set_query(
+        &mut self,
+        graph: &CompactGraph,
+        source: u32,
+        target: u32,
+    ) -> Result<(), RoutingError> {
+        if graph.number_of_nodes() != self.number_of_nodes {
+            return Err(RoutingError::NodeCountMismatch {
+                expected: graph.number_of_nodes(),
+                found: self.number_of_nodes,
+            });
+        }
+
+        self.set_target(target)?;
+
+        if source as usize >= self.number_of_nodes {
+            return Err(RoutingError::NodeIndexOutOfRange(source));
+        }
+
+        if let Some(limit) = self.active_limit {
+            let database = &self.landmark_database;
+
+            //best bound first, the lower index on ties
+            self.active.sort_by_key(|landmark| {
+                std::cmp::Reverse(single_landmark_bound(database, *landmark, source, target))
+            });
+            self.active.truncate(limit.max(1));
+        }
+
+        Ok(())
+    }
+
+    fn estimate(&mut self, _: &CompactGraph, node: u32) -> BastPriorityValue {
+        self.bound(node)
+    }
+
+    //adds the best inactive landmark at a checkpoint if it bounds the settled node clearly better,
+    //a maximum over more landmarks is still feasible
+    fn note_settled(&mut self, node: u32) -> bool {
+        self.settled_in_query += 1;
+
+        //checkpoints at 64, 128, 256, ... settled nodes, each adds at most one landmark
+        if !self.add_during_search
+            || self.settled_in_query < 64
+            || !self.settled_in_query.is_power_of_two()
+            || self.active.len() >= self.number_of_landmarks()
+        {
+            return false;
+        }
+
+        let Some(target) = self.target else {
+            return false;
+        };
+
+        let current = self.bound(node);
+
+        let best_inactive = (0..self.number_of_landmarks())
+            .filter(|landmark| !self.active.contains(landmark))
+            .map(|landmark| {
+                (
+                    single_landmark_bound(&self.landmark_database, landmark, node, target),
+                    std::cmp::Reverse(landmark),
+                )
+            })
+            .max();
+
+        match best_inactive {
+            //only worth it if the bound gets at least one percent better
+            Some((bound, std::cmp::Reverse(landmark)))
+                if BastPriorityValue::Some(bound - bound / 101) > current =>
+            {
+                self.active.push(landmark);
+                self.round += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// How well the landmark bounds guide the search, averaged over random queries.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeuristicQuality {
+    /// How many random queries were run.
+    pub queries: usize,
+    /// Lower bound at the source divided by the distance to the target, 1 is exact.
+    /// Only over queries with a reachable target other than the source.
+    pub mean_bound_ratio: f64,
+    /// Nodes plain Dijkstra settled per query.
+    pub mean_settled_dijkstra: f64,
+    /// Nodes ALT settled per query.
+    pub mean_settled_alt: f64,
+}
+
+impl HeuristicQuality {
+    /// How many times fewer nodes ALT settles than plain Dijkstra.
+    pub fn speedup(&self) -> f64 {
+        self.mean_settled_dijkstra / self.mean_settled_alt.max(1.0)
+    }
+}
+
+/// Runs `number_of_queries` random queries with and without the landmarks and compares the searches,
+/// the landmark searches use a [`LandmarkPotential`] like [`crate::AltRouter`] does.
+pub fn evaluate_landmarks(
+    graph: &CompactGraph,
+    landmark_database: Arc<LandmarkDatabase>,
+    number_of_queries: usize,
+    seed: u64,
+) -> Result<HeuristicQuality, RoutingError> {
+    let number_of_nodes = graph.number_of_nodes();
+
+    if number_of_nodes == 0 {
+        return Err(RoutingError::EmptyGraph);
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut potential = LandmarkPotential::new(graph, landmark_database);
+
+    let mut bound_ratio_sum = 0.0;
+    let mut bounded_queries = 0;
+    let mut settled_dijkstra = 0;
+    let mut settled_alt = 0;
+
+    for _ in 0..number_of_queries {
+        let source = rng.gen_range(0..number_of_nodes) as u32;
+        let target = rng.gen_range(0..number_of_nodes) as u32;
+        let config = SearchConfig::to_target(target);
+
+        let plain = DijkstrasAlgorithm::search_graph(graph, source, &config)?;
+        settled_dijkstra += plain.settled.len();
+
+        potential.set_query(graph, source, target)?;
+
+        if let (BastPriorityValue::Some(distance), BastPriorityValue::Some(bound)) = (
+            plain.distances[target as usize],
+            potential.estimate(graph, source),
+        ) {
+            if distance > 0 {
+                bound_ratio_sum += bound as f64 / distance as f64;
+                bounded_queries += 1;
+            }
+        }
+
+        settled_alt +=
+            DijkstrasAlgorithm::search_graph_with_potential(graph, source, &config, &mut potential)?
+                .settled
+                .len();
+    }
+
+    let queries = number_of_queries.max(1) as f64;
+
+    Ok(HeuristicQuality {
+        queries: number_of_queries,
+        mean_bound_ratio: bound_ratio_sum / bounded_queries.max(1) as f64,
+        mean_settled_dijkstra: settled_dijkstra as f64 / queries,
+        mean_settled_alt: settled_alt as f64 / queries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{coordinate_grid_test_graph, oneway_grid_test_graph};
+
+    fn grid() -> CompactGraph {
+        CompactGraph::from(&coordinate_grid_test_graph(12))
+    }
+
+    #[test]
+    fn selection_strategies() {
+        let graph = grid();
+
+        for selection in [
+            LandmarkSelection::Random { seed: 1 },
+            LandmarkSelection::Farthest { seed: 1 },
+            LandmarkSelection::Avoid { seed: 1 },
+            LandmarkSelection::Planar,
+            LandmarkSelection::MaxCover { seed: 1 },
+        ] {
+            let landmarks = select_landmarks(&graph, 8, selection);
+
+            let mut distinct = landmarks.clone();
+            distinct.sort();
+            distinct.dedup();
+
+            assert_eq!(distinct.len(), 8, "{:?}", selection);
+            assert!(landmarks.iter().all(|node| (*node as usize) < 144));
+            assert_eq!(landmarks, select_landmarks(&graph, 8, selection));
+        }
+
+        assert_eq!(
+            select_landmarks(&graph, 200, LandmarkSelection::Planar).len(),
+            144
+        );
+        assert!(
+            select_landmarks(&CompactGraph::default(), 4, LandmarkSelection::default()).is_empty()
+        );
+
+        //on a grid the first farthest nodes and the sector extremes are on the border
+        let on_border = |node: &u32| {
+            let id = graph.osm_id(*node);
+            id / 12 == 0 || id / 12 == 11 || id % 12 == 0 || id % 12 == 11
+        };
+        //later ones may also fill gaps inside, the uneven costs bend the distances
+        assert!(
+            select_landmarks(&graph, 4, LandmarkSelection::Farthest { seed: 3 })
+                .iter()
+                .take(2)
+                .all(on_border)
+        );
+        assert!(select_landmarks(&graph, 4, LandmarkSelection::Planar)
+            .iter()
+            .all(on_border));
+    }
+
+    #[test]
+    fn lazy_potential_matches_table() {
+        let graph = grid();
+        let landmark_database = Arc::new(precompute_landmark_distances(
+            &graph,
+            5,
+            LandmarkSelection::Farthest { seed: 2 },
+        ));
+
+        let mut potential = LandmarkPotential::new(&graph, landmark_database.clone());
+        assert_eq!(potential.estimate(&graph, 7), BastPriorityValue::Some(0));
+
+        for target in [0, 77, 143, 77] {
+            potential.set_target(target).unwrap();
+
+            let table =
+                transform_landmark_db_into_heuristic(&graph, &landmark_database, target).unwrap();
+
+            //twice, the second time from the memo
+            for _ in 0..2 {
+                for node in (0..144).rev() {
+                    assert_eq!(potential.estimate(&graph, node), table[node as usize]);
+                }
+            }
+        }
+
+        assert!(matches!(
+            potential.set_target(144),
+            Err(RoutingError::NodeIndexOutOfRange(144))
+        ));
+    }
+
+    #[test]
+    fn directed_bounds_are_admissible() {
+        let graph = CompactGraph::from(&oneway_grid_test_graph(10));
+        let landmark_database =
+            precompute_landmark_distances(&graph, 4, LandmarkSelection::Avoid { seed: 4 });
+
+        //the same landmarks without the distances to them
+        let mut from_only = landmark_database.clone();
+        for arr in from_only.distances_to.iter_mut() {
+            arr.fill(BastPriorityValue::Infinity);
+        }
+
+        //distances to a target are distances from it on the reversed graph
+        let mut reversed_dijk = DijkstrasAlgorithm::new(graph.reversed());
+        let mut tighter = 0;
+
+        for target in 0..100 {
+            let bounds =
+                transform_landmark_db_into_heuristic(&graph, &landmark_database, target).unwrap();
+            let from_only_bounds =
+                transform_landmark_db_into_heuristic(&graph, &from_only, target).unwrap();
+
+            let distances_to_target = reversed_dijk
+                .search(target, &SearchConfig::default())
+                .unwrap()
+                .distances;
+
+            for node in 0..100 {
+                assert!(bounds[node] <= distances_to_target[node]);
+                assert!(bounds[node] >= from_only_bounds[node]);
+
+                if bounds[node] > from_only_bounds[node] {
+                    tighter += 1;
+                }
+            }
+        }
+
+        assert!(tighter > 0);
+    }
+
+    #[test]
+    fn active_landmarks() {
+        let graph = CompactGraph::from(&oneway_grid_test_graph(12));
+        let landmark_database = Arc::new(precompute_landmark_distances(
+            &graph,
+            8,
+            LandmarkSelection::Avoid { seed: 6 },
+        ));
+
+        let mut all = LandmarkPotential::new(&graph, landmark_database.clone());
+        let mut best_two =
+            LandmarkPotential::new(&graph, landmark_database).with_active_landmarks(2, false);
+
+        for (source, target) in [(0, 143), (143, 0), (70, 3), (12, 100)] {
+            all.set_query(&graph, source, target).unwrap();
+            best_two.set_query(&graph, source, target).unwrap();
+
+            assert_eq!(all.active_landmarks().len(), 8);
+            assert_eq!(best_two.active_landmarks().len(), 2);
+            //the best landmark for the query is active, so the bound at the source is as good as with all
+            assert_eq!(best_two.estimate(&graph, source), all.estimate(&graph, source));
+
+            for node in 0..144 {
+                assert!(best_two.estimate(&graph, node) <= all.estimate(&graph, node));
+            }
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let graph = grid();
+        let landmark_database =
+            precompute_landmark_distances(&graph, 3, LandmarkSelection::Avoid { seed: 8 });
+
+        let path = std::env::temp_dir().join(format!("bast-landmarks-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        landmark_database.save(path, &graph).unwrap();
+        assert_eq!(
+            LandmarkDatabase::load(path, &graph).unwrap(),
+            landmark_database
+        );
+
+        //same nodes, one edge weight differs
+        let mut other_graph = graph.clone();
+        other_graph.weight[0] += 1;
+        assert!(matches!(
+            LandmarkDatabase::load(path, &other_graph),
+            Err(RoutingError::GraphMismatch {
+                kind: FileKind::LandmarkDatabase,
+                ..
+            })
+        ));
+
+        let bytes = std::fs::read(path).unwrap();
+
+        std::fs::write(path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(matches!(
+            LandmarkDatabase::load(path, &graph),
+            Err(RoutingError::InvalidFile {
+                kind: FileKind::LandmarkDatabase,
+                ..
+            })
+        ));
+
+        let mut newer_version = bytes.clone();
+        newer_version[8] = 2;
+        std::fs::write(path, &newer_version).unwrap();
+        assert!(matches!(
+            LandmarkDatabase::load(path, &graph),
+            Err(RoutingError::InvalidFile {
+                kind: FileKind::LandmarkDatabase,
+                ..
+            })
+        ));
+
+        //a landmark count whose file length overflows
+        let mut corrupt_count = bytes.clone();
+        corrupt_count[28..36].copy_from_slice(&(u64::MAX / 4).to_le_bytes());
+        std::fs::write(path, &corrupt_count).unwrap();
+        assert!(matches!(
+            LandmarkDatabase::load(path, &graph),
+            Err(RoutingError::InvalidFile {
+                kind: FileKind::LandmarkDatabase,
+                reason: "wrong file length"
+            })
+        ));
+
+        std::fs::write(path, b"not landmarks").unwrap();
+        assert!(matches!(
+            LandmarkDatabase::load(path, &graph),
+            Err(RoutingError::InvalidFile {
+                kind: FileKind::LandmarkDatabase,
+                ..
+            })
+        ));
+
+        let mut missing_distances_to = landmark_database.clone();
+        missing_distances_to.distances_to.pop();
+        assert!(matches!(
+            missing_distances_to.save(path, &graph),
+            Err(RoutingError::InvalidFile {
+                kind: FileKind::LandmarkDatabase,
+                reason: "not one distance table per landmark"
+            })
+        ));
+
+        let mut missing_node = landmark_database.clone();
+        missing_node.distances[1].pop();
+        assert!(matches!(
+            missing_node.save(path, &graph),
+            Err(RoutingError::NodeCountMismatch {
+                expected: 144,
+                found: 143
+            })
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn thread_count_does_not_change_distances() {
+        let graph = CompactGraph::from(&oneway_grid_test_graph(12));
+        let landmarks = select_landmarks(&graph, 7, LandmarkSelection::Random { seed: 10 });
+
+        let single_threaded = compute_landmark_distances(&graph, landmarks.clone(), 1);
+
+        assert_eq!(single_threaded.distances.len(), 7);
+        assert_eq!(single_threaded.distances_to.len(), 7);
+        assert_eq!(single_threaded.number_of_landmarks(), 7);
+        assert_eq!(
+            single_threaded.distances[3],
+            DijkstrasAlgorithm::new(graph.clone())
+                .search(landmarks[3], &SearchConfig::default())
+                .unwrap()
+                .distances
+        );
+
+        for number_of_threads in [2, 3, 16] {
+            assert_eq!(
+                compute_landmark_distances(&graph, landmarks.clone(), number_of_threads),
+                single_threaded
+            );
+        }
+
+        assert!(compute_landmark_distances(&graph, vec![], 4)
+            .landmarks
+            .is_empty());
+    }
+
+    #[test]
+    fn selection_distances_are_reused() {
+        let graph = CompactGraph::from(&oneway_grid_test_graph(12));
+
+        for selection in [
+            LandmarkSelection::Farthest { seed: 11 },
+            LandmarkSelection::Avoid { seed: 11 },
+            LandmarkSelection::Planar,
+        ] {
+            let (landmarks, distances) = select_landmarks_with_distances(&graph, 5, selection);
+            assert_eq!(landmarks, select_landmarks(&graph, 5, selection));
+            assert_eq!(
+                distances.iter().all(Option::is_some),
+                selection != LandmarkSelection::Planar
+            );
+
+            assert_eq!(
+                precompute_landmark_distances(&graph, 5, selection),
+                compute_landmark_distances(&graph, landmarks, 1)
+            );
+        }
+    }
+
+    #[test]
+    fn heuristic_quality() {
+        let graph = grid();
+
+        for selection in [
+            LandmarkSelection::Farthest { seed: 5 },
+            LandmarkSelection::Avoid { seed: 5 },
+            LandmarkSelection::Planar,
+            LandmarkSelection::MaxCover { seed: 5 },
+        ] {
+            let landmark_database = Arc::new(precompute_landmark_distances(&graph, 6, selection));
+            let quality = evaluate_landmarks(&graph, landmark_database, 40, 9).unwrap();
+
+            assert_eq!(quality.queries, 40);
+            assert!(quality.mean_bound_ratio > 0.0 && quality.mean_bound_ratio <= 1.0);
+            assert!(quality.speedup() > 1.0, "{:?} {:?}", selection, quality);
+        }
+    }
+}
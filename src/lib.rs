@@ -1,97 +1,125 @@
-use core::num;
+//! Shortest path queries on OpenStreetMap road networks.
+//!
+//! Load a [`RoadNetwork`] with [`RoadNetwork::read_from_osm_file`], convert it into a
+//! [`CompactGraph`], then query it through the [`Router`] trait, either with plain Dijkstra
+//! ([`DijkstrasAlgorithm`]), with Dijkstra from both ends ([`BidirectionalDijkstra`]), with A* and
+//! landmarks ([`AltRouter`], or from both ends [`BidirectionalAlt`]), with A* and straight line
+//! distances ([`GeometricRouter`]) or on a [`ContractionHierarchy`].
+
 use geoutils::Location;
-use osmpbf::elements::Way;
-use osmpbf::elements::WayNodeLocation;
-use osmpbf::{Element, ElementReader};
 use priority_queue::DoublePriorityQueue;
 use std::collections::{HashMap, HashSet};
-use std::error::Error;
 use std::sync::Arc;
 
-use rand::Rng;
-use std::time::Instant;
-
-mod road_network;
-
-use crate::road_network::speed_from_way_kmh;
-use crate::road_network::RoadNetwork;
+pub mod arc_flags;
+pub mod bidirectional;
+pub mod compact_graph;
+pub mod contraction_hierarchy;
+pub mod error;
+mod file_format;
+pub mod geometric;
+pub mod hub_labels;
+pub mod landmarks;
+pub mod matrix;
+pub mod maxspeed;
+pub mod metric;
+mod parallel;
+pub mod potential;
+pub mod profile;
+pub mod road_network;
+pub mod router;
+pub mod transit_nodes;
+
+use crate::metric::Segment;
 use crate::road_network::SimplifiedWay;
-
-struct DijkstrasAlgorithm {
-    graph: RoadNetwork,
-    //the value is the round number
-    visited_node_marks: HashMap<i64, usize>,
-    number_of_completed_rounds: usize,
-    heuristic: Option<Arc<HashMap<i64, BastPriorityValue>>>,
+use osmpbfreader::Tags;
+
+pub use crate::arc_flags::{ArcFilter, ArcFlags, ArcFlagsRouter};
+pub use crate::bidirectional::{BidirectionalAlt, BidirectionalDijkstra, BidirectionalResult};
+pub use crate::compact_graph::CompactGraph;
+pub use crate::contraction_hierarchy::ContractionHierarchy;
+pub use crate::error::{FileKind, RoutingError};
+pub use crate::geometric::GeometricPotential;
+pub use crate::hub_labels::HubLabels;
+pub use crate::potential::{Potential, TablePotential, ZeroPotential};
+pub use crate::metric::{CostUnit, Metric};
+pub use crate::profile::{BicycleProfile, CarProfile, FootProfile, Profile};
+pub use crate::landmarks::{
+    compute_landmark_distances, evaluate_landmarks, precompute_landmark_distances, select_landmarks,
+    transform_landmark_db_into_heuristic, HeuristicQuality, LandmarkDatabase, LandmarkPotential,
+    LandmarkSelection,
+};
+pub use crate::matrix::{distance_matrix, distance_matrix_with_hierarchy, DistanceMatrix};
+pub use crate::road_network::RoadNetwork;
+pub use crate::transit_nodes::TransitNodeRouting;
+pub use crate::router::{AStarRouter, AltRouter, GeometricRouter, RouteQuery, RouteResult, Router};
+
+/// Dijkstra's algorithm over a [`CompactGraph`], turns into A* once a heuristic is set.
+pub struct DijkstrasAlgorithm {
+    /// The graph every search runs on.
+    pub graph: CompactGraph,
+    //strongly connected component of each node, empty until find_largest_connected_component() ran
+    components: Vec<usize>,
+    //used by search(), None is plain Dijkstra
+    heuristic: Option<Box<dyn Potential + Send>>,
 }
 
+/// A route through the graph as returned by a query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShortestPath {
+    /// OSM node ids from source to target, both included.
+    pub path: Vec<i64>,
+    /// Cost of each edge along the path, `edge_costs[i]` is the edge `path[i] -> path[i + 1]`.
+    pub edge_costs: Vec<u32>,
+    /// Sum of `edge_costs`.
+    pub cost: u32,
+}
 
-fn precompute_landmark_distances(
-    graph: &RoadNetwork,
-    number_of_landmarks: usize,
-    //landmark.node -> distance
-) -> HashMap<i64, HashMap<i64, BastPriorityValue>> {
-    let landmarks: Vec<i64> = graph
-        .nodes
-        .iter()
-        .take(number_of_landmarks)
-        .cloned()
-        .collect();
-
-    let mut distances_from_each_landmark: HashMap<i64, HashMap<i64, BastPriorityValue>> =
-        HashMap::new();
-
-    let mut dijk = DijkstrasAlgorithm {
-        graph: graph.clone(),
-        visited_node_marks: HashMap::new(),
-        number_of_completed_rounds: 0,
-        heuristic: None,
-    };
-
-    for landmark in landmarks {
-       // println!("landmark finding for {}", landmark);
-
-        let all_distances = dijk.compute_shortest_path(landmark, -1).1;
-
-        distances_from_each_landmark.insert(landmark, all_distances);
-    }
-
-    distances_from_each_landmark
+/// When a search is allowed to stop, nodes are given by their index in the [`CompactGraph`].
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum StoppingCriterion {
+    /// Run until all nodes reachable from the source are settled.
+    #[default]
+    SettleAll,
+    /// Stop as soon as the target node is settled.
+    Target(u32),
+    /// Settle exactly the nodes at most the radius away, nodes beyond it are not expanded.
+    CostRadius(u32),
+    /// Stop once this many nodes are settled.
+    SettledNodes(usize),
+    /// Stop once every node of the set is settled.
+    TargetSet(HashSet<u32>),
 }
 
-fn transform_landmark_db_into_heuristic(
-    road_network: &RoadNetwork,
-    landmark_database: &HashMap<i64, HashMap<i64, BastPriorityValue>>,
-    target: i64,
-) -> HashMap<i64, BastPriorityValue> {
-    
-    road_network.nodes.iter().map(|source| {
-        (*source, {
-            //look at all landmarks,
-                //the heuristic for each node is the largest difference between (the source and the landmark) vs (the landmark and the source)
-            landmark_database.iter().map(|(_, arr)| {
-                
-                let distance_lu = *arr.get(source).unwrap();
-
-                let distance_tu = *arr.get(&target).unwrap();
-
-                match (distance_lu, distance_tu) {
-                    (BastPriorityValue::Some(lu), BastPriorityValue::Some(tu)) => BastPriorityValue::Some(lu.abs_diff(tu)),
-                    _ => BastPriorityValue::Some(0)
-                }
-            }).max().unwrap()
-        })
-    }).collect()
+/// Settings of a single search, the default settles every reachable node.
+#[derive(Clone, Debug, Default)]
+pub struct SearchConfig {
+    /// When the search stops.
+    pub stopping_criterion: StoppingCriterion,
+    /// Arcs the filter does not allow are never relaxed, None relaxes all of them.
+    pub arc_filter: Option<ArcFilter>,
 }
 
-struct ShortestPath {
-    path: Vec<i64>,
-    cost: u32,
+/// Everything a search found before it stopped, indexed by node.
+pub struct SearchResult {
+    /// Distance from the source, known as gscore. Nodes which were reached but not settled
+    /// before the search stopped only have an upper bound here.
+    pub distances: Vec<BastPriorityValue>,
+    /// Predecessor on the shortest path, the source and unreached nodes have None.
+    pub prev: Vec<Option<u32>>,
+    /// Nodes in the order they were settled.
+    pub settled: Vec<u32>,
+    /// How many arcs of the settled nodes the arc filter skipped.
+    pub pruned_arcs: usize,
+    is_settled: Vec<bool>,
 }
 
+/// A path cost, unreached nodes are at `Infinity`.
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
-enum BastPriorityValue {
+pub enum BastPriorityValue {
+    /// No path is known.
     Infinity,
+    /// The cost of a path.
     Some(u32),
 }
 
@@ -160,256 +188,446 @@ impl Ord for BastPriorityValue {
     }
 }
 
-impl DijkstrasAlgorithm {
-    //Compute the shortest path from source to target, returns the cost
-    // run until all nodes reachable from the source are settled
-    // -1 as target id tries to settle all nodes
+impl SearchConfig {
+    /// Stops as soon as `target` is settled.
+    pub fn to_target(target: u32) -> SearchConfig {
+        SearchConfig {
+            stopping_criterion: StoppingCriterion::Target(target),
+            ..SearchConfig::default()
+        }
+    }
+}
 
-    pub fn reduce_to_largest_connected_component(&mut self) -> () {
+impl SearchResult {
+    /// Whether the search settled `node`, fails with [`RoutingError::NodeIndexOutOfRange`]
+    /// if the searched graph has no such node.
+    pub fn is_settled(&self, node: u32) -> Result<bool, RoutingError> {
+        self.is_settled
+            .get(node as usize)
+            .copied()
+            .ok_or(RoutingError::NodeIndexOutOfRange(node))
+    }
 
-        let largest_connected_component = self.find_largest_connected_component();
+    /// The route to `target`, None if the search did not settle it.
+    pub fn path_to(
+        &self,
+        graph: &CompactGraph,
+        target: u32,
+    ) -> Result<Option<ShortestPath>, RoutingError> {
+        if !self.is_settled(target)? {
+            return Ok(None);
+        }
 
-        
-        println!("calculated component, deleting....");
+        //walk the predecessors back from the target, then flip it around
+        let mut path: Vec<u32> = vec![target];
+        let mut current = target;
 
-        //delete all nodes and corrosponding edges which are not in the largest connected component
+        while let Some(previous) = self.prev[current as usize] {
+            path.push(previous);
+            current = previous;
+        }
 
-        for (marked_node_id, iteration_component) in self.visited_node_marks.iter() {
-            if *iteration_component != largest_connected_component {
-                //delete this
+        path.reverse();
 
-                self.graph.edges.remove(marked_node_id);
-                self.graph.nodes.remove(marked_node_id);
-            }
+        Ok(Some(ShortestPath::from_node_indices(graph, path)))
+    }
+}
+
+impl ShortestPath {
+    //path is given by node index, every consecutive pair has to be an edge of the graph
+    pub(crate) fn from_node_indices(graph: &CompactGraph, path: Vec<u32>) -> ShortestPath {
+        let edge_costs: Vec<u32> = path
+            .windows(2)
+            .map(|pair| graph.edge_weight(pair[0], pair[1]).unwrap())
+            .collect();
+
+        ShortestPath {
+            cost: edge_costs.iter().sum(),
+            path: path.into_iter().map(|node| graph.osm_id(node)).collect(),
+            edge_costs,
         }
     }
+}
 
-    pub fn find_largest_connected_component(&mut self) -> usize {
-        while self
-            .visited_node_marks
-            .iter()
-            .any(|node_mark| *node_mark.1 == 0)
-        {
-            let pick_source_id = self
-                .visited_node_marks
-                .iter()
-                .find(|node_mark| *node_mark.1 == 0)
-                .unwrap()
-                .0.clone();
+impl DijkstrasAlgorithm {
+    /// Plain Dijkstra on `graph`, without a heuristic.
+    pub fn new(graph: CompactGraph) -> DijkstrasAlgorithm {
+        DijkstrasAlgorithm {
+            graph,
+            components: vec![],
+            heuristic: None,
+        }
+    }
 
-            // println!(
-            //    "Round {}, Now attempting to path {}",
-            //     self.number_of_completed_rounds, pick_source_id
-            //);
-         //   println!("Finding from {}", pick_source_id);
-            self.compute_shortest_path(pick_source_id, -1);
-           // println!("Found all nodes from {}", pick_source_id);
+    /// Removes every node outside of the largest connected component from the graph.
+    pub fn reduce_to_largest_connected_component(&mut self) -> Result<(), RoutingError> {
+        let largest_connected_component = self.find_largest_connected_component()?;
 
-           if self.visited_node_marks.iter().filter(|(node_id, round)| **round == self.number_of_completed_rounds).count() > (self.graph.nodes.len() / 2) + 1 {
-            break;
-           }
+        //delete all nodes and corrosponding edges which are not in the largest connected component
+        //this renumbers the nodes, so the components and any heuristic are reset as well
+        let graph = self
+            .graph
+            .subgraph(|node| self.components[node as usize] == largest_connected_component);
+
+        *self = DijkstrasAlgorithm::new(graph);
+
+        Ok(())
+    }
+
+    /// Returns the id of the largest strongly connected component and remembers the component of every node.
+    pub fn find_largest_connected_component(&mut self) -> Result<usize, RoutingError> {
+        if self.graph.number_of_nodes() == 0 {
+            return Err(RoutingError::EmptyGraph);
         }
 
-        //scan through visited node marks to make a ranking table
+        //edges are directed, so a search from a node only finds what it can reach, not what can reach it back
+        self.components = self
+            .graph
+            .strongly_connected_components()
+            .into_iter()
+            .map(|component| component as usize)
+            .collect();
 
-        let mut round_to_number_of_nodes: HashMap<usize, usize> = HashMap::new();
+        //scan through the components to make a ranking table
 
-        for (mark_node_id, mark_round_number) in self.visited_node_marks.iter() {
-            round_to_number_of_nodes
-                .entry(*mark_round_number)
+        let mut component_to_number_of_nodes: HashMap<usize, usize> = HashMap::new();
+
+        for component in self.components.iter() {
+            component_to_number_of_nodes
+                .entry(*component)
                 .and_modify(|x| *x += 1)
                 .or_insert(1);
         }
 
-        let mut sorted_round_order: Vec<(usize, usize)> =
-            round_to_number_of_nodes.into_iter().collect();
+        let mut sorted_components: Vec<(usize, usize)> =
+            component_to_number_of_nodes.into_iter().collect();
 
-        sorted_round_order.sort_by_key(|k| k.1);
+        sorted_components.sort_by_key(|k| k.1);
 
-        sorted_round_order.reverse();
+        sorted_components.reverse();
 
-        println!(
-            "Largest connected components with node count: {:?}",
-            sorted_round_order
-        );
-
-        sorted_round_order[0].0
+        Ok(sorted_components[0].0)
     }
 
-    //return the cost and all distances
+    /// Cost from `source` to `target` by OSM id, along with the distances of every node the search reached.
     pub fn compute_shortest_path(
         &mut self,
         source: i64,
         target: i64,
-    ) -> (BastPriorityValue, HashMap<i64, BastPriorityValue>) {
-        self.number_of_completed_rounds = self.number_of_completed_rounds + 1;
-
-        // used for finding the largest connected component
-        self.visited_node_marks
-            .insert(source, self.number_of_completed_rounds);
-
-        //create vertex priority queue Q
-        let mut pq: DoublePriorityQueue<i64, BastPriorityValue> = DoublePriorityQueue::new();
-
-        //known as gscore
-        let mut distances: HashMap<i64, BastPriorityValue> = HashMap::new();
-
-        // Predecessor data store
-        // called cameFrom on A* page
-        let mut prev: HashMap<i64, Option<i64>> = HashMap::new();
-
-        distances.insert(source, BastPriorityValue::Some(0));
-
-        //initialisation
-        distances.insert(
-            source,
-            match &self.heuristic {
-                Some(table) => match table.get(&source) {
-                    Some(initial_value) => *initial_value,
-                    None => BastPriorityValue::Some(0),
-                },
-                None => BastPriorityValue::Some(0),
-            },
-        );
-        // associated priority equals dist[·]
-        pq.push(source.clone(), BastPriorityValue::Some(0));
-
-        for node in self.graph.nodes.iter() {
-            if node != &source {
-                prev.insert(node.clone(), None); // Predecessor of v
-                                                 //save on memory, don't insert nothing, if nothing is found, state that the node is infinite distance
-                                                 //distances.insert(node.clone(), BastPriorityValue::Infinity);  // Unknown distance from source to v
-                                                 //pq.push(node.clone(), BastPriorityValue::Infinity);
+    ) -> Result<(BastPriorityValue, Vec<BastPriorityValue>), RoutingError> {
+        let source = self
+            .graph
+            .index_of(source)
+            .ok_or(RoutingError::UnknownNode(source))?;
+        let target = self
+            .graph
+            .index_of(target)
+            .ok_or(RoutingError::UnknownNode(target))?;
+
+        self.prepare_heuristic(source, target)?;
+        let distances = self.search(source, &SearchConfig::to_target(target))?.distances;
+
+        //return the cost of the target node
+        Ok((distances[target as usize], distances))
+    }
+
+    /// Route from `source` to `target` by OSM id.
+    pub fn shortest_path(
+        &mut self,
+        source: i64,
+        target: i64,
+    ) -> Result<ShortestPath, RoutingError> {
+        let source_index = self
+            .graph
+            .index_of(source)
+            .ok_or(RoutingError::UnknownNode(source))?;
+        let target_index = self
+            .graph
+            .index_of(target)
+            .ok_or(RoutingError::UnknownNode(target))?;
+
+        self.prepare_heuristic(source_index, target_index)?;
+
+        self.search(source_index, &SearchConfig::to_target(target_index))?
+            .path_to(&self.graph, target_index)?
+            .ok_or(RoutingError::UnreachableTarget { source, target })
+    }
+
+    //search() does not know the target, the point to point queries prepare the heuristic for it
+    fn prepare_heuristic(&mut self, source: u32, target: u32) -> Result<(), RoutingError> {
+        match self.heuristic.as_mut() {
+            Some(heuristic) => heuristic.set_query(&self.graph, source, target),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs the search from node index `source` until the stopping criterion of `config` is met,
+    /// guided by the heuristic if one is set.
+    ///
+    /// Fails with [`RoutingError::NodeIndexOutOfRange`] if the source or a target of the stopping
+    /// criterion is not a node of the graph, as do the other searches.
+    pub fn search(
+        &mut self,
+        source: u32,
+        config: &SearchConfig,
+    ) -> Result<SearchResult, RoutingError> {
+        match self.heuristic.take() {
+            Some(mut heuristic) => {
+                let result = self.search_with_potential(source, config, heuristic.as_mut());
+                self.heuristic = Some(heuristic);
+                result
             }
+            None => self.search_with_potential(source, config, &mut ZeroPotential),
         }
+    }
 
-        //the main loop
-        while !pq.is_empty() {
-            //  println!("pq {} items remaining", pq.len());
-            // Remove ;and return best vertex
-            //u ← Q.extract_min()
-            if let Some(u) = pq.pop_min() {
-                //  println!("Checking node {} with priority {:?}", u.0, u.1);
-                // Go through all v neighbours of u
-                if let Some(neighbours) = self.graph.edges.get(&u.0) {
-                    //   println!("Checking neighbours for {}: {:?}", u.0, neighbours);
-                    for v in neighbours {
-                        //u.0 is the node id
-                        //distances.get(&u.0).unwrap().clone() is cost of node u
-                        //v.1 is cost for v pair, v.0 is the node id
-                        let u_dist = match distances.get(&u.0) {
-                            Some(u_dist) => *u_dist,
-                            None => BastPriorityValue::Infinity,
-                        };
+    /// A* with `potential`, which has to be prepared for the query with [`Potential::set_query`].
+    pub fn search_with_potential<P: Potential + ?Sized>(
+        &self,
+        source: u32,
+        config: &SearchConfig,
+        potential: &mut P,
+    ) -> Result<SearchResult, RoutingError> {
+        checked_search(&self.graph, potential, source, config)
+    }
 
-                        //tentative_gScore
-                        let alt = u_dist + BastPriorityValue::Some(*v.1);
+    /// Plain Dijkstra on a graph which is not owned by a `DijkstrasAlgorithm`,
+    /// e.g. from several threads over one shared graph.
+    pub fn search_graph(
+        graph: &CompactGraph,
+        source: u32,
+        config: &SearchConfig,
+    ) -> Result<SearchResult, RoutingError> {
+        checked_search(graph, &mut ZeroPotential, source, config)
+    }
 
-                        let dist_v = match distances.get(&v.0) {
-                            Some(dist_v) => dist_v,
-                            None => &BastPriorityValue::Infinity,
-                        };
-                        //if the new distance is better than the previously stored distance for this node
-                        if alt < *dist_v {
-                            prev.insert(*v.0, Some(u.0));
-
-                            distances.insert(*v.0, alt);
-
-                            //Instead of filling the priority queue with all nodes in the initialization phase,
-                            // it is also possible to initialize it to contain only source;
-                            //then, inside the if alt < dist[v] block,
-                            //the decrease_priority() becomes an add_with_priority() operation if the node is not already in the queue
-
-                            let new_queue_value = match &self.heuristic {
-                                None => alt,
-                                //get the heuristic of the neighbour
-                                Some(table) => match table.get(v.0) {
-                                    None => alt,
-                                    Some(heuristic_for_neighbour) => alt + *heuristic_for_neighbour,
-                                },
-                            };
+    /// A* with `potential` on a graph which is not owned by a `DijkstrasAlgorithm`.
+    pub fn search_graph_with_potential<P: Potential + ?Sized>(
+        graph: &CompactGraph,
+        source: u32,
+        config: &SearchConfig,
+        potential: &mut P,
+    ) -> Result<SearchResult, RoutingError> {
+        checked_search(graph, potential, source, config)
+    }
 
-                            pq.push(*v.0, new_queue_value);
-                        }
-                    }
+    /// Sets lower bounds towards a target node index as A* heuristic, None goes back to plain Dijkstra.
+    /// Queries to any other target fail with [`RoutingError::TargetMismatch`].
+    pub fn change_heuristic(
+        self: &mut DijkstrasAlgorithm,
+        new_heuristic: Option<(Arc<Vec<BastPriorityValue>>, u32)>,
+    ) {
+        self.set_heuristic(new_heuristic.map(|(table, target)| {
+            Box::new(TablePotential::new(table, target)) as Box<dyn Potential + Send>
+        }));
+    }
+
+    /// Sets the potential search() uses, it has to be prepared for each query with [`Potential::set_query`].
+    /// compute_shortest_path() and shortest_path() do that themselves.
+    pub fn set_heuristic(&mut self, heuristic: Option<Box<dyn Potential + Send>>) {
+        self.heuristic = heuristic;
+    }
+}
+
+//run_search() after checking that the source and the targets of the stopping criterion are nodes
+fn checked_search<P: Potential + ?Sized>(
+    graph: &CompactGraph,
+    potential: &mut P,
+    source: u32,
+    config: &SearchConfig,
+) -> Result<SearchResult, RoutingError> {
+    graph.check_node(source)?;
+
+    match &config.stopping_criterion {
+        StoppingCriterion::Target(target) => graph.check_node(*target)?,
+        StoppingCriterion::TargetSet(targets) => {
+            for target in targets {
+                graph.check_node(*target)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(run_search(graph, potential, source, config))
+}
+
+//the search behind DijkstrasAlgorithm::search(), for node indices which are known to be in the graph
+//distances are the real costs from the source, the potential only goes into the priorities
+pub(crate) fn run_search<P: Potential + ?Sized>(
+    graph: &CompactGraph,
+    potential: &mut P,
+    source: u32,
+    config: &SearchConfig,
+) -> SearchResult {
+    let number_of_nodes = graph.number_of_nodes();
+
+    //create vertex priority queue Q
+    let mut pq: DoublePriorityQueue<u32, BastPriorityValue> = DoublePriorityQueue::new();
+
+    //known as gscore
+    let mut distances: Vec<BastPriorityValue> =
+        vec![BastPriorityValue::Infinity; number_of_nodes];
+
+    // Predecessor data store
+    // called cameFrom on A* page
+    let mut prev: Vec<Option<u32>> = vec![None; number_of_nodes];
+
+    let mut settled: Vec<u32> = vec![];
+    let mut is_settled: Vec<bool> = vec![false; number_of_nodes];
+    let mut pruned_arcs = 0;
+
+    //how many nodes of the target set still have to be settled
+    let mut remaining_targets = match &config.stopping_criterion {
+        StoppingCriterion::TargetSet(targets) => targets.len(),
+        _ => 0,
+    };
+
+    //initialisation
+    distances[source as usize] = BastPriorityValue::Some(0);
+    // associated priority equals dist[·] + potential
+    pq.push(source, potential.estimate(graph, source));
+
+    //the main loop
+    // Remove and return best vertex
+    //u ← Q.extract_min()
+    while let Some((u, _)) = pq.pop_min() {
+        //  println!("Checking node {} with priority {:?}", u, priority);
+        let u_dist = distances[u as usize];
+
+        if let StoppingCriterion::CostRadius(radius) = config.stopping_criterion {
+            if u_dist > BastPriorityValue::Some(radius) {
+                //without a potential every node still in the queue is at least as far away
+                if potential.is_zero() {
+                    break;
                 }
+
+                //the queue is ordered by distance plus potential, so nodes within the radius can still come
+                //after this one, it is only left out and the queue runs dry on its own
+                continue;
             }
         }
 
-        //  println!("distances {:?}", distances);
+        if !is_settled[u as usize] {
+            is_settled[u as usize] = true;
+            settled.push(u);
 
-        for distance_store in distances.iter() {
-            match distance_store.1 {
-                BastPriorityValue::Some(distance) => {
-                    // used for finding the largest connected component
-                    self.visited_node_marks
-                        .insert(*distance_store.0, self.number_of_completed_rounds);
+            if let StoppingCriterion::TargetSet(targets) = &config.stopping_criterion {
+                if targets.contains(&u) {
+                    remaining_targets -= 1;
+                }
+            }
+
+            //the bounds got tighter (e.g. more landmarks were activated), the queued nodes are ordered by them
+            //the bounds stay feasible, so the nodes settled so far keep their distances
+            if potential.note_settled(u) {
+                let queued: Vec<u32> = pq.iter().map(|(node, _)| *node).collect();
+
+                for node in queued {
+                    let priority = distances[node as usize] + potential.estimate(graph, node);
+                    pq.change_priority(&node, priority);
                 }
-                _ => {}
             }
         }
 
-        //return the cost of the target node
-        (
-            match distances.get(&target) {
-                Some(target_cost) => *target_cost,
-                None => BastPriorityValue::Infinity,
-            },
-            distances,
-        )
+        let done = match &config.stopping_criterion {
+            StoppingCriterion::SettleAll | StoppingCriterion::CostRadius(_) => false,
+            StoppingCriterion::Target(target) => u == *target,
+            StoppingCriterion::SettledNodes(limit) => settled.len() >= *limit,
+            StoppingCriterion::TargetSet(_) => remaining_targets == 0,
+        };
+
+        if done {
+            break;
+        }
+
+        // Go through all v neighbours of u
+        for edge in graph.edge_range(u) {
+            if let Some(arc_filter) = &config.arc_filter {
+                if !arc_filter.allows(edge) {
+                    pruned_arcs += 1;
+                    continue;
+                }
+            }
+
+            let (v, cost) = (graph.head[edge], graph.weight[edge]);
+
+            //tentative_gScore
+            let alt = u_dist + BastPriorityValue::Some(cost);
+
+            //if the new distance is better than the previously stored distance for this node
+            if alt < distances[v as usize] {
+                prev[v as usize] = Some(u);
+
+                distances[v as usize] = alt;
+
+                //Instead of filling the priority queue with all nodes in the initialization phase,
+                // it is also possible to initialize it to contain only source;
+                //then, inside the if alt < dist[v] block,
+                //the decrease_priority() becomes an add_with_priority() operation if the node is not already in the queue
+
+                //get the heuristic of the neighbour
+                let new_queue_value = alt + potential.estimate(graph, v);
+
+                pq.push(v, new_queue_value);
+            }
+        }
     }
 
-    
-fn change_heuristic(self: &mut DijkstrasAlgorithm, new_heuristic: Option<Arc<HashMap<i64, BastPriorityValue>>>) {
-    self.heuristic = new_heuristic;
-}
+    //  println!("distances {:?}", distances);
+
+    SearchResult {
+        distances,
+        prev,
+        settled,
+        pruned_arcs,
+        is_settled,
+    }
 }
 
 impl RoadNetwork {
-    pub fn read_from_osm_file(path: &str) -> Result<RoadNetwork, Box<dyn Error>> {
+    /// Builds the road network of `profile` from a `.osm.pbf` file, edge weights are computed by `metric`.
+    pub fn read_from_osm_file(
+        path: &str,
+        profile: &dyn Profile,
+        metric: &Metric,
+    ) -> Result<RoadNetwork, RoutingError> {
         let mut graph = RoadNetwork::new();
-
-        let mut way_counter: u32 = 0;
-        let mut node_counter: u32 = 0;
-        let mut dense_node_counter: u32 = 0;
+        graph.unit = metric.unit();
 
         let path_cleaned = std::path::Path::new(&path);
-        let r = std::fs::File::open(&path_cleaned).unwrap();
+        let r = std::fs::File::open(path_cleaned)?;
 
         let mut pbf = osmpbfreader::OsmPbfReader::new(r);
 
-        let mut new_way_counter: u32 = 0;
-        let mut new_node_counter: u32 = 0;
-
         use osmpbfreader::objects::OsmObj;
         let mut ways: Vec<SimplifiedWay> = vec![];
 
         let mut nodes_hashmap: HashMap<i64, Location> = HashMap::new();
 
-        for obj in pbf.iter().map(Result::unwrap) {
-            match obj {
+        for obj in pbf.iter() {
+            match obj? {
                 OsmObj::Node(node) => {
-                    new_node_counter = new_node_counter + 1;
                     //  graph.nodes.insert(node.id.0);
                     nodes_hashmap.insert(node.id.0, Location::new(node.lat(), node.lon()));
                 }
                 OsmObj::Way(way) => {
-                    new_way_counter = new_way_counter + 1;
-
-                    if let Some(speed) = speed_from_way_kmh(&way) {
-                        let speed_metres_per_second: f32 = speed as f32 * (5.0 / 18.0);
+                    if let Some(speeds) = profile.way_speeds(&way.tags) {
                         // println!("node ref like: {:?}", way.raw_refs());
 
                         if way.nodes.len() >= 2 {
                             ways.push(SimplifiedWay {
-                                node_sequence: Vec::from_iter(
-                                    way.nodes
-                                        .into_iter()
-                                        .map(|x| x.0.clone())
-                                        .collect::<Vec<i64>>(),
-                                ),
+                                node_sequence: way
+                                    .nodes
+                                    .into_iter()
+                                    .map(|x| x.0)
+                                    .collect::<Vec<i64>>(),
                                 id: way.id.0,
-                                highway_speed_m_per_s: speed_metres_per_second,
+                                forward_speed_kmh: speeds.forward_kmh,
+                                backward_speed_kmh: speeds.backward_kmh,
+                                tags: match metric.needs_tags() {
+                                    true => way.tags,
+                                    false => Tags::new(),
+                                },
                             });
                         }
                     }
@@ -418,12 +636,6 @@ impl RoadNetwork {
             }
         }
 
-        println!(
-            "{} new nodes, {} new ways",
-            new_node_counter, new_way_counter
-        );
-        println!("{} simplified way count", ways.len());
-
         for way in ways {
             let mut previous_head_node_location_now_tail_location: Option<&Location> = None;
             let mut previous_head_node_index: usize = 0;
@@ -449,67 +661,179 @@ impl RoadNetwork {
                         let distance_metres =
                             tail_location.haversine_distance_to(head_location).meters();
 
-                        let speed_metres_per_second: f32 =
-                            way.highway_speed_m_per_s as f32 * (5.0 / 18.0);
-                        let cost = (distance_metres / speed_metres_per_second as f64) as u32;
+                        let cost = |speed_kmh: f32| {
+                            metric.cost(&Segment {
+                                length_metres: distance_metres,
+                                speed_kmh,
+                                tags: &way.tags,
+                            })
+                        };
 
                         let tail_id = way.node_sequence[i];
                         let head_id = way.node_sequence[i + 1];
 
-                        graph
-                            .edges
-                            .entry(tail_id)
-                            .and_modify(|edge_list| {
-                                edge_list.insert(head_id, cost);
-                            })
-                            .or_insert({
-                                let mut a = HashMap::new();
-                                a.insert(head_id, cost);
-                                a
-                            });
+                        //only the directions the way may be used in
+                        if let Some(speed) = way.forward_speed_kmh {
+                            graph.insert_edge(tail_id, head_id, cost(speed));
+                            graph.max_speed_kmh = graph.max_speed_kmh.max(speed);
+                        }
 
-                        graph
-                            .edges
-                            .entry(head_id)
-                            .and_modify(|edge_list| {
-                                edge_list.insert(tail_id, cost);
-                            })
-                            .or_insert({
-                                let mut a = HashMap::new();
-                                a.insert(tail_id, cost);
-                                a
-                            });
+                        if let Some(speed) = way.backward_speed_kmh {
+                            graph.insert_edge(head_id, tail_id, cost(speed));
+                            graph.max_speed_kmh = graph.max_speed_kmh.max(speed);
+                        }
 
                         //save back to prevent relookup
-                        previous_head_node_location_now_tail_location = Some(&head_location);
+                        previous_head_node_location_now_tail_location = Some(head_location);
                         previous_head_node_index = i + 1;
                     }
                 }
             }
         }
 
-        //new node insertion process
-        let new_nodes: HashSet<i64> =
-            HashSet::from_iter(graph.edges.iter().map(|(node_id, _)| node_id.clone()));
-
-        graph.nodes = new_nodes;
+        //keep the location of the nodes which ended up in the graph
+        graph.coordinates = graph
+            .nodes
+            .iter()
+            .filter_map(|node_id| nodes_hashmap.get(node_id).map(|location| (*node_id, *location)))
+            .collect();
 
-        println!("{} in nodes_hashmap", nodes_hashmap.len());
+        if graph.edges.is_empty() {
+            return Err(RoutingError::EmptyGraph);
+        }
 
         Ok(graph)
     }
+}
+
+#[cfg(test)]
+pub(crate) mod test_helpers {
+    use super::*;
+
+    //  1 --4--> 2 --1--> 3
+    //  |                 ^
+    //  +-------7---------+     4 is not connected
+    pub(crate) fn small_test_graph() -> RoadNetwork {
+        let mut graph = RoadNetwork::new();
+
+        for (tail, head, cost) in [(1, 2, 4), (2, 3, 1), (1, 3, 7)] {
+            graph.insert_edge(tail, head, cost);
+            graph.insert_edge(head, tail, cost);
+        }
+
+        graph.nodes.insert(4);
+
+        for node in 1..=4 {
+            graph
+                .coordinates
+                .insert(node, Location::new(48.0 + 0.001 * (node - 1) as f64, 9.0));
+        }
+
+        graph
+    }
+
+    //n x n grid, node id is row * n + column, edges in both directions with uneven costs
+    pub(crate) fn grid_test_graph(n: i64) -> RoadNetwork {
+        let mut graph = RoadNetwork::new();
+
+        for row in 0..n {
+            for column in 0..n {
+                let node = row * n + column;
+                let cost = ((row * 7 + column * 13) % 10 + 1) as u32;
+
+                if column + 1 < n {
+                    graph.insert_edge(node, node + 1, cost);
+                    graph.insert_edge(node + 1, node, cost);
+                }
+
+                if row + 1 < n {
+                    graph.insert_edge(node, node + n, cost + 2);
+                    graph.insert_edge(node + n, node, cost + 2);
+                }
+            }
+        }
+
+        graph
+    }
+
+    //grid_test_graph with a coordinate for every node, about 100 m apart in both directions
+    pub(crate) fn coordinate_grid_test_graph(n: i64) -> RoadNetwork {
+        let mut graph = grid_test_graph(n);
+
+        for node in 0..n * n {
+            graph.coordinates.insert(
+                node,
+                Location::new(
+                    48.0 + 0.0009 * (node / n) as f64,
+                    9.0 + 0.0013 * (node % n) as f64,
+                ),
+            );
+        }
+
+        graph
+    }
+
+    //grid_test_graph where even rows can only be driven to the right and every third column only downwards
+    pub(crate) fn oneway_grid_test_graph(n: i64) -> RoadNetwork {
+        let mut graph = grid_test_graph(n);
 
-    pub fn new() -> RoadNetwork {
-        RoadNetwork {
-            nodes: HashSet::new(),
-            edges: HashMap::new(),
+        for row in 0..n {
+            for column in 0..n {
+                let node = row * n + column;
+
+                if row % 2 == 0 && column + 1 < n {
+                    graph.edges.get_mut(&(node + 1)).unwrap().remove(&node);
+                }
+
+                if column % 3 == 0 && row + 1 < n {
+                    graph.edges.get_mut(&(node + n)).unwrap().remove(&node);
+                }
+            }
         }
+
+        graph
+    }
+
+    //routes between node ids step by step over 0..number_of_nodes with both routers, the same cost
+    //and the same unreachable targets, returns how many nodes each of them settled
+    pub(crate) fn assert_same_routes(
+        expected: &mut dyn Router,
+        found: &mut dyn Router,
+        number_of_nodes: i64,
+    ) -> (usize, usize) {
+        let mut settled_expected = 0;
+        let mut settled_found = 0;
+
+        for source in (0..number_of_nodes).step_by(5) {
+            for target in (0..number_of_nodes).step_by(7) {
+                let query = RouteQuery { source, target };
+
+                match (expected.route(&query), found.route(&query)) {
+                    (Ok(expected), Ok(found)) => {
+                        assert_eq!(found.path.cost, expected.path.cost);
+                        assert_eq!(found.path.path.first(), Some(&source));
+                        assert_eq!(found.path.path.last(), Some(&target));
+
+                        settled_expected += expected.settled_nodes;
+                        settled_found += found.settled_nodes;
+                    }
+                    (Err(RoutingError::UnreachableTarget { .. }), found) => {
+                        assert!(matches!(found, Err(RoutingError::UnreachableTarget { .. })))
+                    }
+                    (expected, found) => panic!("{:?} != {:?}", found, expected),
+                }
+            }
+        }
+
+        (settled_expected, settled_found)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_helpers::{grid_test_graph, small_test_graph};
+    use std::time::Instant;
 
     /*
     #[test]
@@ -525,38 +849,25 @@ mod tests {
         //    println!("{} | {:?}", node_id, edges);
         //}
 
-        let initial_visited_node_marks = {
-            let mut visited_node_marks: HashMap<i64, usize> = HashMap::new();
-
-            for node in &graph.nodes {
-                visited_node_marks.insert(*node, 0);
-            }
-
-            visited_node_marks
-        };
-
         assert!(graph.nodes.contains(&1834861939));
         assert!(graph.nodes.contains(&3710901043));
 
         //   println!("Edges next to {}, {:?}", 122976558,graph.edges.get(&122976558));
 
-        let mut routing = DijkstrasAlgorithm {
-            graph: graph,
-            visited_node_marks: initial_visited_node_marks,
-            number_of_completed_rounds: 0,
-            heuristic: None,
-        };
+        let mut routing = DijkstrasAlgorithm::new(CompactGraph::from(&graph));
 
-        let route_between_shen_and_ben = routing.compute_shortest_path(1834861939, 3710901043);
+        let route_between_shen_and_ben = routing
+            .compute_shortest_path(1834861939, 3710901043)
+            .unwrap();
 
         println!(
-            "Cost in seconds between Shen and Ben {:?}",
+            "Cost in milliseconds between Shen and Ben {:?}",
             route_between_shen_and_ben.0
         );
 
         //find largest connected component
         let start_connected_component_compute = Instant::now();
-        let largest_connected_component = routing.find_largest_connected_component();
+        routing.find_largest_connected_component().unwrap();
 
         let end_connected_component_compute_time = Instant::now();
 
@@ -571,29 +882,14 @@ mod tests {
     fn bast_baden_wuerttemberg() {
         let graph = test_osm("./bast-baden-wuerttemberg.pbf");
 
-        let initial_visited_node_marks = {
-            let mut visited_node_marks: HashMap<i64, usize> = HashMap::new();
-
-            for node in &graph.nodes {
-                visited_node_marks.insert(*node, 0);
-            }
-
-            visited_node_marks
-        };
-
-        let mut routing = DijkstrasAlgorithm {
-            graph: graph.clone(),
-            visited_node_marks: initial_visited_node_marks,
-            number_of_completed_rounds: 0,
-            heuristic: None,
-        };
+        let mut routing = DijkstrasAlgorithm::new(CompactGraph::from(&graph));
 
         println!("Now trying to find largest connected component of Baden-Württemberg");
 
         //find largest connected component
         let start_connected_component_compute = Instant::now();
 
-        let reduce_to_largest_connected_component = routing.reduce_to_largest_connected_component();
+        routing.reduce_to_largest_connected_component().unwrap();
 
         let end_connected_component_compute_time = Instant::now();
 
@@ -605,27 +901,200 @@ mod tests {
         let pick_rand_end = routing.graph.get_random_node();
 
         let compute_landmarks_timer = Instant::now();
-        let landmark_database = precompute_landmark_distances(&routing.graph, 42);
+        //reuse the landmarks of an earlier run on the same graph
+        let landmark_file = "./bast-baden-wuerttemberg.landmarks";
+        let landmark_database = Arc::new(match LandmarkDatabase::load(landmark_file, &routing.graph) {
+            Ok(landmark_database) => landmark_database,
+            Err(error) => {
+                println!("Not using {}: {}", landmark_file, error);
+                let landmark_database = precompute_landmark_distances(&routing.graph, 42, LandmarkSelection::Avoid { seed: 42 });
+                landmark_database.save(landmark_file, &routing.graph).unwrap();
+                landmark_database
+            }
+        });
         println!("Computing landmarks for Baden-Württemberg {:?}", compute_landmarks_timer.elapsed());
 
+        let quality = evaluate_landmarks(&routing.graph, landmark_database.clone(), 20, 7).unwrap();
+        println!("Landmark quality for Baden-Württemberg {:?}, speedup {:.1}", quality, quality.speedup());
+        assert!(quality.speedup() > 1.0);
+
         let compute_h_timer = Instant::now();
-        let calculate_heuristic = transform_landmark_db_into_heuristic(&routing.graph, &landmark_database, pick_rand_end);
+        //only the best few of the 42 landmarks for this query, more are added when they help
+        let mut potential = LandmarkPotential::new(&routing.graph, landmark_database).with_active_landmarks(4, true);
+        potential.set_query(&routing.graph, routing.graph.index_of(pick_rand_start).unwrap(), routing.graph.index_of(pick_rand_end).unwrap()).unwrap();
         println!("Computing heuristic for Baden-Württemberg {:?}", compute_h_timer.elapsed());
 
-        let h = Arc::new(calculate_heuristic);
-        routing.change_heuristic(Some(h));
+        routing.set_heuristic(Some(Box::new(potential)));
 
         //run query
 
         let compute_a_star_with_landmarks_timer = Instant::now();
-        routing.compute_shortest_path(pick_rand_start, pick_rand_end);
+        routing.compute_shortest_path(pick_rand_start, pick_rand_end).unwrap();
         println!("Computing a star with landmarks shortest path for Baden-Württemberg {:?}", compute_a_star_with_landmarks_timer.elapsed());
 
     }
 
+    #[test]
+    fn shortest_path_returns_route() {
+        let mut routing = DijkstrasAlgorithm::new(CompactGraph::from(&small_test_graph()));
+
+        let route = routing.shortest_path(1, 3).unwrap();
+
+        assert_eq!(route.path, vec![1, 2, 3]);
+        assert_eq!(route.edge_costs, vec![4, 1]);
+        assert_eq!(route.cost, 5);
+        assert_eq!(
+            routing.compute_shortest_path(1, 3).unwrap().0,
+            BastPriorityValue::Some(route.cost)
+        );
+
+        let to_itself = routing.shortest_path(2, 2).unwrap();
+        assert_eq!(to_itself.path, vec![2]);
+        assert_eq!(to_itself.cost, 0);
+
+        assert!(matches!(
+            routing.shortest_path(1, 4),
+            Err(RoutingError::UnreachableTarget {
+                source: 1,
+                target: 4
+            })
+        ));
+        assert!(matches!(
+            routing.shortest_path(1, 5),
+            Err(RoutingError::UnknownNode(5))
+        ));
+        assert!(matches!(
+            routing.compute_shortest_path(5, 1),
+            Err(RoutingError::UnknownNode(5))
+        ));
+    }
+
+    #[test]
+    fn reduce_to_largest_connected_component() {
+        let mut routing = DijkstrasAlgorithm::new(CompactGraph::from(&small_test_graph()));
+
+        routing.reduce_to_largest_connected_component().unwrap();
+
+        assert_eq!(routing.graph.osm_ids, vec![1, 2, 3]);
+        assert_eq!(routing.graph.number_of_edges(), 6);
+        assert_eq!(
+            routing.compute_shortest_path(3, 1).unwrap().0,
+            BastPriorityValue::Some(5)
+        );
+    }
+
+    #[test]
+    fn stopping_criteria() {
+        let mut routing = DijkstrasAlgorithm::new(CompactGraph::from(&small_test_graph()));
+
+        //node indices are the osm ids minus one
+        let mut settle = |stopping_criterion: StoppingCriterion| {
+            let mut settled: Vec<i64> = routing
+                .search(
+                    0,
+                    &SearchConfig {
+                        stopping_criterion,
+                        ..SearchConfig::default()
+                    },
+                )
+                .unwrap()
+                .settled
+                .into_iter()
+                .map(|node| node as i64 + 1)
+                .collect();
+            settled.sort();
+            settled
+        };
+
+        assert_eq!(settle(StoppingCriterion::SettleAll), vec![1, 2, 3]);
+        assert_eq!(settle(StoppingCriterion::Target(1)), vec![1, 2]);
+        assert_eq!(settle(StoppingCriterion::CostRadius(4)), vec![1, 2]);
+        assert_eq!(settle(StoppingCriterion::CostRadius(3)), vec![1]);
+        assert_eq!(settle(StoppingCriterion::SettledNodes(2)), vec![1, 2]);
+        assert_eq!(
+            settle(StoppingCriterion::TargetSet(HashSet::from([0, 2]))),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn node_indices_out_of_range() {
+        let graph = CompactGraph::from(&small_test_graph());
+        let mut routing = DijkstrasAlgorithm::new(graph.clone());
+
+        assert!(matches!(
+            routing.search(99, &SearchConfig::default()),
+            Err(RoutingError::NodeIndexOutOfRange(99))
+        ));
+        assert!(matches!(
+            DijkstrasAlgorithm::search_graph(&graph, 0, &SearchConfig::to_target(4)),
+            Err(RoutingError::NodeIndexOutOfRange(4))
+        ));
+        assert!(matches!(
+            routing.search(
+                0,
+                &SearchConfig {
+                    stopping_criterion: StoppingCriterion::TargetSet(HashSet::from([1, 7])),
+                    ..SearchConfig::default()
+                }
+            ),
+            Err(RoutingError::NodeIndexOutOfRange(7))
+        ));
+
+        let result = routing.search(0, &SearchConfig::default()).unwrap();
+        assert!(matches!(
+            result.is_settled(4),
+            Err(RoutingError::NodeIndexOutOfRange(4))
+        ));
+        assert!(matches!(
+            result.path_to(&graph, 4),
+            Err(RoutingError::NodeIndexOutOfRange(4))
+        ));
+        assert_eq!(result.path_to(&graph, 3).unwrap(), None);
+    }
+
+    #[test]
+    fn cost_radius_with_potential() {
+        let graph = CompactGraph::from(&grid_test_graph(10));
+        let radius = 20;
+
+        let within_radius = |result: &SearchResult| {
+            let mut nodes: Vec<u32> = (0..100)
+                .filter(|node| result.distances[*node as usize] <= BastPriorityValue::Some(radius))
+                .collect();
+            nodes.sort();
+            nodes
+        };
+
+        let all = DijkstrasAlgorithm::search_graph(&graph, 0, &SearchConfig::default()).unwrap();
+
+        //exact distances to the far corner, nodes towards it come first in the queue
+        let table =
+            DijkstrasAlgorithm::search_graph(&graph, 99, &SearchConfig::default())
+                .unwrap()
+                .distances;
+
+        let config = SearchConfig {
+            stopping_criterion: StoppingCriterion::CostRadius(radius),
+            ..SearchConfig::default()
+        };
+        let result = DijkstrasAlgorithm::new(graph).search_with_potential(
+            0,
+            &config,
+            &mut TablePotential::new(Arc::new(table), 99),
+        )
+        .unwrap();
+
+        let mut settled = result.settled.clone();
+        settled.sort();
+
+        assert_eq!(settled, within_radius(&all));
+        assert_eq!(within_radius(&result), within_radius(&all));
+    }
+
     fn test_osm(path: &str) -> RoadNetwork {
         let start = Instant::now();
-        let graph = RoadNetwork::read_from_osm_file(path);
+        let graph = RoadNetwork::read_from_osm_file(path, &CarProfile, &Metric::TravelTime);
         let elapsed = start.elapsed();
         println!("{} Elapsed: {:.2?}", path, elapsed);
         assert!(graph.is_ok());
@@ -0,0 +1,194 @@
+//! Many-to-many cost tables.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    run_search, BastPriorityValue, CompactGraph, ContractionHierarchy, RoutingError, SearchConfig,
+    StoppingCriterion, ZeroPotential,
+};
+
+/// Costs from every source to every target, row by row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DistanceMatrix {
+    /// OSM node ids in the order of the rows.
+    pub sources: Vec<i64>,
+    /// OSM node ids in the order of the columns.
+    pub targets: Vec<i64>,
+    //costs[source * targets.len() + target]
+    costs: Vec<BastPriorityValue>,
+}
+
+impl DistanceMatrix {
+    /// Cost from `sources[source]` to `targets[target]`, `Infinity` if there is no path.
+    pub fn get(&self, source: usize, target: usize) -> BastPriorityValue {
+        self.costs[source * self.targets.len() + target]
+    }
+
+    /// Costs from `sources[source]` to every target.
+    pub fn row(&self, source: usize) -> &[BastPriorityValue] {
+        &self.costs[source * self.targets.len()..(source + 1) * self.targets.len()]
+    }
+}
+
+/// Cost table between the OSM node ids `sources` and `targets`, with a Dijkstra search per source.
+pub fn distance_matrix(
+    graph: &CompactGraph,
+    sources: &[i64],
+    targets: &[i64],
+) -> Result<DistanceMatrix, RoutingError> {
+    let (source_indices, target_indices) = node_indices(graph, sources, targets)?;
+
+    Ok(DistanceMatrix {
+        sources: sources.to_vec(),
+        targets: targets.to_vec(),
+        costs: dijkstra_costs(graph, &source_indices, &target_indices),
+    })
+}
+
+/// Cost table between the OSM node ids `sources` and `targets` of `hierarchy.graph`.
+///
+/// It runs one upward search per source and per target and meets them in buckets
+/// (Knopp et al., "Computing Many-to-Many Shortest Paths Using Highway Hierarchies").
+pub fn distance_matrix_with_hierarchy(
+    hierarchy: &ContractionHierarchy,
+    sources: &[i64],
+    targets: &[i64],
+) -> Result<DistanceMatrix, RoutingError> {
+    let (source_indices, target_indices) = node_indices(&hierarchy.graph, sources, targets)?;
+
+    Ok(DistanceMatrix {
+        sources: sources.to_vec(),
+        targets: targets.to_vec(),
+        costs: bucket_costs(hierarchy, &source_indices, &target_indices),
+    })
+}
+
+fn node_indices(
+    graph: &CompactGraph,
+    sources: &[i64],
+    targets: &[i64],
+) -> Result<(Vec<u32>, Vec<u32>), RoutingError> {
+    let index_of = |osm_id: &i64| {
+        graph
+            .index_of(*osm_id)
+            .ok_or(RoutingError::UnknownNode(*osm_id))
+    };
+
+    Ok((
+        sources.iter().map(index_of).collect::<Result<_, _>>()?,
+        targets.iter().map(index_of).collect::<Result<_, _>>()?,
+    ))
+}
+
+//row by row like DistanceMatrix, sources and targets are node indices
+pub(crate) fn bucket_costs(
+    hierarchy: &ContractionHierarchy,
+    sources: &[u32],
+    targets: &[u32],
+) -> Vec<BastPriorityValue> {
+    //for every node of a backward search space: (column, distance from the node to that target)
+    let mut buckets: HashMap<u32, Vec<(usize, u32)>> = HashMap::new();
+
+    for (column, target) in targets.iter().enumerate() {
+        for (node, distance) in hierarchy.search_space(*target, false) {
+            buckets.entry(node).or_default().push((column, distance));
+        }
+    }
+
+    let mut costs = vec![BastPriorityValue::Infinity; sources.len() * targets.len()];
+
+    for (row, source) in sources.iter().enumerate() {
+        let row_costs = &mut costs[row * targets.len()..(row + 1) * targets.len()];
+
+        //the highest node of each shortest path is in both search spaces
+        for (node, distance) in hierarchy.search_space(*source, true) {
+            for (column, to_target) in buckets.get(&node).into_iter().flatten() {
+                row_costs[*column] =
+                    row_costs[*column].min(BastPriorityValue::Some(distance + to_target));
+            }
+        }
+    }
+
+    costs
+}
+
+fn dijkstra_costs(
+    graph: &CompactGraph,
+    sources: &[u32],
+    targets: &[u32],
+) -> Vec<BastPriorityValue> {
+    //each search stops once it has settled all of the targets
+    let config = SearchConfig {
+        stopping_criterion: StoppingCriterion::TargetSet(
+            targets.iter().copied().collect::<HashSet<u32>>(),
+        ),
+        ..SearchConfig::default()
+    };
+
+    sources
+        .iter()
+        .flat_map(|source| {
+            let distances = run_search(graph, &mut ZeroPotential, *source, &config).distances;
+
+            targets
+                .iter()
+                .map(move |target| distances[*target as usize])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DijkstrasAlgorithm;
+    use crate::test_helpers::{oneway_grid_test_graph, small_test_graph};
+
+    #[test]
+    fn both_backends_match_dijkstra() {
+        let graph = CompactGraph::from(&oneway_grid_test_graph(10));
+        let hierarchy = ContractionHierarchy::new(graph.clone());
+        let mut dijkstra = DijkstrasAlgorithm::new(graph.clone());
+
+        //the same node twice and a node which only some sources reach
+        let sources: Vec<i64> = vec![0, 17, 17, 54, 99];
+        let targets: Vec<i64> = vec![3, 40, 0, 77, 98, 99];
+
+        let with_hierarchy = distance_matrix_with_hierarchy(&hierarchy, &sources, &targets).unwrap();
+        let without_hierarchy = distance_matrix(&graph, &sources, &targets).unwrap();
+
+        assert_eq!(with_hierarchy, without_hierarchy);
+        assert_eq!(with_hierarchy.row(1), with_hierarchy.row(2));
+
+        for (row, source) in sources.iter().enumerate() {
+            for (column, target) in targets.iter().enumerate() {
+                let (expected, _) = dijkstra.compute_shortest_path(*source, *target).unwrap();
+                assert_eq!(with_hierarchy.get(row, column), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_and_unreachable_nodes() {
+        let graph = CompactGraph::from(&small_test_graph());
+        let hierarchy = ContractionHierarchy::new(graph.clone());
+
+        let matrix = distance_matrix_with_hierarchy(&hierarchy, &[1, 4], &[3, 4]).unwrap();
+        assert_eq!(
+            matrix.row(0),
+            &[BastPriorityValue::Some(5), BastPriorityValue::Infinity]
+        );
+        assert_eq!(
+            matrix.row(1),
+            &[BastPriorityValue::Infinity, BastPriorityValue::Some(0)]
+        );
+
+        assert!(matches!(
+            distance_matrix(&graph, &[1], &[5]),
+            Err(RoutingError::UnknownNode(5))
+        ));
+        assert!(matches!(
+            distance_matrix_with_hierarchy(&hierarchy, &[5], &[1]),
+            Err(RoutingError::UnknownNode(5))
+        ));
+    }
+}
@@ -0,0 +1,144 @@
+//! Parsing of `maxspeed` tags.
+
+/// Speed used on roads tagged `maxspeed=none`, the recommended speed on German motorways.
+pub const UNLIMITED_SPEED_KMH: f32 = 130.0;
+
+/// A posted speed limit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MaxSpeed {
+    /// A limit, converted to km/h from the unit of the tag.
+    Limit {
+        /// The limit in km/h.
+        kmh: f32,
+    },
+    /// `maxspeed=none`, no legal limit.
+    Unlimited,
+}
+
+impl MaxSpeed {
+    /// The speed to assume on the road, `UNLIMITED_SPEED_KMH` if there is no limit.
+    pub fn kmh(&self) -> f32 {
+        match self {
+            MaxSpeed::Limit { kmh } => *kmh,
+            MaxSpeed::Unlimited => UNLIMITED_SPEED_KMH,
+        }
+    }
+}
+
+/// Parses a value of the `maxspeed` tag, None if it is malformed or not a fixed limit (e.g. `signals`).
+///
+/// Understands plain numbers (km/h), the units `km/h`, `kmh`, `kph`, `mph` and `knots`,
+/// country zone codes like `DE:urban` and `none`.
+/// Several values separated by `;` give the lowest of them.
+/// See <https://wiki.openstreetmap.org/wiki/Key:maxspeed>.
+pub fn parse_maxspeed(value: &str) -> Option<MaxSpeed> {
+    let value = value.trim();
+
+    if value.contains(';') {
+        return value
+            .split(';')
+            .map(parse_maxspeed)
+            .collect::<Option<Vec<MaxSpeed>>>()?
+            .into_iter()
+            .min_by(|a, b| a.kmh().total_cmp(&b.kmh()));
+    }
+
+    if value == "none" {
+        return Some(MaxSpeed::Unlimited);
+    }
+
+    if value == "walk" {
+        return Some(MaxSpeed::Limit { kmh: 7.0 });
+    }
+
+    if value.contains(':') {
+        return zone_speed(value);
+    }
+
+    let number_end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+
+    let number: f32 = value[..number_end].parse().ok()?;
+
+    let kmh = match value[number_end..].trim() {
+        "" | "km/h" | "kmh" | "kph" => number,
+        "mph" => number * 1.609_344,
+        "knots" => number * 1.852,
+        _ => return None,
+    };
+
+    valid_limit(kmh)
+}
+
+//a limit of zero, infinity or NaN would break the travel time of the edge
+fn valid_limit(kmh: f32) -> Option<MaxSpeed> {
+    if kmh > 0.0 && kmh.is_finite() {
+        Some(MaxSpeed::Limit { kmh })
+    } else {
+        None
+    }
+}
+
+//implicit limits like DE:urban, see https://wiki.openstreetmap.org/wiki/Speed_limits_by_country
+fn zone_speed(value: &str) -> Option<MaxSpeed> {
+    let (country, zone) = value.split_once(':')?;
+
+    let limit = |kmh: f32| Some(MaxSpeed::Limit { kmh });
+
+    //DE:zone30 and DE:zone:30 are both in use
+    if let Some(speed) = zone.strip_prefix("zone") {
+        return valid_limit(speed.trim_start_matches(':').parse().ok()?);
+    }
+
+    match (country, zone) {
+        (_, "living_street") => limit(7.0),
+        (_, "bicycle_road") => limit(30.0),
+        (_, "urban") => limit(50.0),
+        ("DE", "rural") | ("AT", "rural") => limit(100.0),
+        ("CH", "rural") | ("FR", "rural") => limit(80.0),
+        ("DE", "motorway") => Some(MaxSpeed::Unlimited),
+        ("AT", "motorway") | ("FR", "motorway") => limit(130.0),
+        ("CH", "motorway") => limit(120.0),
+        ("CH", "trunk") | ("AT", "trunk") => limit(100.0),
+        ("FR", "trunk") => limit(110.0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kmh(value: &str) -> Option<f32> {
+        parse_maxspeed(value).map(|speed| (speed.kmh() * 10.0).round() / 10.0)
+    }
+
+    #[test]
+    fn maxspeed_values() {
+        assert_eq!(kmh("50"), Some(50.0));
+        assert_eq!(kmh("50 km/h"), Some(50.0));
+        assert_eq!(kmh("80kmh"), Some(80.0));
+        assert_eq!(kmh("30 mph"), Some(48.3));
+        assert_eq!(kmh("10 knots"), Some(18.5));
+        assert_eq!(kmh("DE:urban"), Some(50.0));
+        assert_eq!(kmh("DE:rural"), Some(100.0));
+        assert_eq!(kmh("DE:zone30"), Some(30.0));
+        assert_eq!(kmh("DE:zone:20"), Some(20.0));
+        assert_eq!(kmh("DE:living_street"), Some(7.0));
+        assert_eq!(kmh("CH:motorway"), Some(120.0));
+        assert_eq!(kmh("60;80"), Some(60.0));
+        assert_eq!(parse_maxspeed("none"), Some(MaxSpeed::Unlimited));
+        assert_eq!(parse_maxspeed("DE:motorway"), Some(MaxSpeed::Unlimited));
+
+        assert_eq!(kmh("signals"), None);
+        assert_eq!(kmh("fast"), None);
+        assert_eq!(kmh("0"), None);
+        assert_eq!(kmh("50 furlongs"), None);
+        assert_eq!(kmh("XX:urban2"), None);
+        assert_eq!(kmh("60;signals"), None);
+        assert_eq!(kmh("DE:zone:0"), None);
+        assert_eq!(kmh("DE:zoneinf"), None);
+        assert_eq!(kmh("DE:zoneNaN"), None);
+    }
+}
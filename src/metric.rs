@@ -0,0 +1,103 @@
+//! What the edge costs of a graph measure.
+
+use osmpbfreader::Tags;
+
+/// Unit of the edge weights of a graph.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CostUnit {
+    /// Travel time.
+    #[default]
+    Milliseconds,
+    /// Geometric length.
+    Metres,
+    /// Whatever a custom metric returns.
+    Custom,
+}
+
+/// One edge of a way as seen by a metric.
+pub struct Segment<'a> {
+    /// Great-circle length of the edge.
+    pub length_metres: f64,
+    /// Speed the profile allows on the way in this direction.
+    pub speed_kmh: f32,
+    /// Tags of the way, only filled in for custom metrics.
+    pub tags: &'a Tags,
+}
+
+/// How the cost of an edge is computed at import time.
+pub enum Metric {
+    /// Travel time in milliseconds.
+    TravelTime,
+    /// Length in metres.
+    Distance,
+    /// A user supplied cost, the graph gets the unit [`CostUnit::Custom`].
+    Custom(Box<dyn Fn(&Segment) -> u32 + Send + Sync>),
+}
+
+impl Metric {
+    /// The unit the edge costs of a graph imported with this metric are in.
+    pub fn unit(&self) -> CostUnit {
+        match self {
+            Metric::TravelTime => CostUnit::Milliseconds,
+            Metric::Distance => CostUnit::Metres,
+            Metric::Custom(_) => CostUnit::Custom,
+        }
+    }
+
+    /// Whether the importer has to keep the tags of every way for this metric.
+    pub fn needs_tags(&self) -> bool {
+        matches!(self, Metric::Custom(_))
+    }
+
+    /// Cost of the segment, rounded up so that no edge of a road is free.
+    pub fn cost(&self, segment: &Segment) -> u32 {
+        match self {
+            Metric::TravelTime => {
+                let metres_per_second = segment.speed_kmh as f64 / 3.6;
+                ((segment.length_metres / metres_per_second) * 1000.0)
+                    .ceil()
+                    .max(1.0) as u32
+            }
+            Metric::Distance => segment.length_metres.ceil().max(1.0) as u32,
+            Metric::Custom(cost_function) => cost_function(segment),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(tags: &Tags, length_metres: f64, speed_kmh: f32) -> Segment<'_> {
+        Segment {
+            length_metres,
+            speed_kmh,
+            tags,
+        }
+    }
+
+    #[test]
+    fn costs_and_units() {
+        let tags = Tags::new();
+
+        //100 m at 36 km/h are 10 s
+        assert_eq!(
+            Metric::TravelTime.cost(&segment(&tags, 100.0, 36.0)),
+            10_000
+        );
+        assert_eq!(Metric::TravelTime.cost(&segment(&tags, 2.5, 50.0)), 180);
+        assert_eq!(Metric::TravelTime.cost(&segment(&tags, 0.0, 50.0)), 1);
+        assert_eq!(Metric::TravelTime.unit(), CostUnit::Milliseconds);
+
+        assert_eq!(Metric::Distance.cost(&segment(&tags, 100.2, 36.0)), 101);
+        assert_eq!(Metric::Distance.cost(&segment(&tags, 0.3, 36.0)), 1);
+        assert_eq!(Metric::Distance.unit(), CostUnit::Metres);
+
+        let double_length = Metric::Custom(Box::new(|segment: &Segment| {
+            (segment.length_metres * 2.0) as u32
+        }));
+        assert_eq!(double_length.cost(&segment(&tags, 100.0, 36.0)), 200);
+        assert_eq!(double_length.unit(), CostUnit::Custom);
+        assert!(double_length.needs_tags());
+    }
+}
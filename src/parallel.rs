@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// All threads the machine offers, 1 if that is unknown.
+pub(crate) fn number_of_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1)
+}
+
+/// Runs the jobs `0..number_of_jobs` on up to `number_of_threads` threads, each thread takes the next
+/// job as soon as it is done with one and folds it into its own accumulator.
+///
+/// Returns the accumulators of all threads, which thread did which job is not deterministic.
+pub(crate) fn parallel_fold<T: Send>(
+    number_of_jobs: usize,
+    number_of_threads: usize,
+    init: impl Fn() -> T + Sync,
+    job: impl Fn(&mut T, usize) + Sync,
+) -> Vec<T> {
+    let next_job = AtomicUsize::new(0);
+
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..number_of_threads.clamp(1, number_of_jobs.max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut accumulator = init();
+
+                    loop {
+                        let next = next_job.fetch_add(1, Ordering::Relaxed);
+
+                        if next >= number_of_jobs {
+                            break accumulator;
+                        }
+
+                        job(&mut accumulator, next);
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect()
+    })
+}
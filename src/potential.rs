@@ -0,0 +1,173 @@
+//! Lower bounds which turn Dijkstra into A*.
+
+use std::sync::Arc;
+
+use crate::compact_graph::CompactGraph;
+use crate::error::RoutingError;
+use crate::BastPriorityValue;
+
+/// Lower bound on the remaining cost to the target, which turns the search into A*.
+///
+/// The search orders nodes by distance plus potential but never mixes the potential into the distances.
+/// It is only correct for feasible potentials: `estimate(u) <= cost(u, v) + estimate(v)` for every edge
+/// and 0 at the target.
+pub trait Potential {
+    /// Prepares the bounds for a query, called before every search.
+    fn set_query(
+        &mut self,
+        graph: &CompactGraph,
+        source: u32,
+        target: u32,
+    ) -> Result<(), RoutingError>;
+
+    /// Lower bound on the cost from `node` to the target.
+    fn estimate(&mut self, graph: &CompactGraph, node: u32) -> BastPriorityValue;
+
+    /// Called for every node the search settles, returns true if the bounds changed
+    /// so the queued nodes have to be ordered again. The new bounds have to stay feasible.
+    fn note_settled(&mut self, _node: u32) -> bool {
+        false
+    }
+
+    /// True if every estimate is 0, the search then settles the nodes in order of their distance.
+    fn is_zero(&self) -> bool {
+        false
+    }
+}
+
+/// No bound at all, the search stays Dijkstra's algorithm.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ZeroPotential;
+
+impl Potential for ZeroPotential {
+    fn set_query(&mut self, _: &CompactGraph, _: u32, _: u32) -> Result<(), RoutingError> {
+        Ok(())
+    }
+
+    fn estimate(&mut self, _: &CompactGraph, _: u32) -> BastPriorityValue {
+        BastPriorityValue::Some(0)
+    }
+
+    fn is_zero(&self) -> bool {
+        true
+    }
+}
+
+/// Bounds which were computed for every node and one fixed target beforehand,
+/// e.g. by [`crate::transform_landmark_db_into_heuristic`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TablePotential {
+    /// The bound of each node, indexed by node.
+    pub table: Arc<Vec<BastPriorityValue>>,
+    /// The node index the bounds lead to, the only target the table can be used for.
+    pub target: u32,
+}
+
+impl TablePotential {
+    /// Bounds `table` towards the node index `target`.
+    pub fn new(table: Arc<Vec<BastPriorityValue>>, target: u32) -> TablePotential {
+        TablePotential { table, target }
+    }
+}
+
+impl Potential for TablePotential {
+    //the target is baked into the table, only check that it is the one of the query and fits the graph
+    fn set_query(&mut self, graph: &CompactGraph, _: u32, target: u32) -> Result<(), RoutingError> {
+        if target != self.target {
+            return Err(RoutingError::TargetMismatch {
+                expected: self.target,
+                found: target,
+            });
+        }
+
+        if self.table.len() != graph.number_of_nodes() {
+            return Err(RoutingError::NodeCountMismatch {
+                expected: graph.number_of_nodes(),
+                found: self.table.len(),
+            });
+        }
+
+        if target as usize >= graph.number_of_nodes() {
+            return Err(RoutingError::NodeIndexOutOfRange(target));
+        }
+
+        Ok(())
+    }
+
+    fn estimate(&mut self, _: &CompactGraph, node: u32) -> BastPriorityValue {
+        self.table[node as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{assert_same_routes, grid_test_graph, oneway_grid_test_graph};
+    use crate::{
+        precompute_landmark_distances, transform_landmark_db_into_heuristic, AStarRouter,
+        DijkstrasAlgorithm, GeometricPotential, LandmarkPotential, LandmarkSelection, Router,
+    };
+
+    #[test]
+    fn distances_do_not_contain_the_heuristic() {
+        let graph = CompactGraph::from(&grid_test_graph(8));
+        let landmark_database =
+            precompute_landmark_distances(&graph, 3, LandmarkSelection::Avoid { seed: 3 });
+
+        let mut plain = DijkstrasAlgorithm::new(graph.clone());
+        let mut guided = DijkstrasAlgorithm::new(graph.clone());
+
+        for (source, target) in [(0, 63), (63, 0), (9, 50)] {
+            let table =
+                transform_landmark_db_into_heuristic(&graph, &landmark_database, target).unwrap();
+            assert!(table[source as usize] > BastPriorityValue::Some(0));
+            guided.change_heuristic(Some((Arc::new(table), target)));
+
+            let (expected, _) = plain
+                .compute_shortest_path(source as i64, target as i64)
+                .unwrap();
+            let (cost, distances) = guided
+                .compute_shortest_path(source as i64, target as i64)
+                .unwrap();
+
+            assert_eq!(cost, expected);
+            assert_eq!(distances[source as usize], BastPriorityValue::Some(0));
+        }
+
+        //the last table leads to node 50
+        assert!(matches!(
+            guided.compute_shortest_path(0, 63),
+            Err(RoutingError::TargetMismatch {
+                expected: 50,
+                found: 63
+            })
+        ));
+    }
+
+    #[test]
+    fn every_potential_matches_dijkstra() {
+        let graph = CompactGraph::from(&oneway_grid_test_graph(9));
+        let landmark_database = Arc::new(precompute_landmark_distances(
+            &graph,
+            4,
+            LandmarkSelection::default(),
+        ));
+
+        let mut dijkstra = DijkstrasAlgorithm::new(graph.clone());
+        let mut routers: Vec<Box<dyn Router>> = vec![
+            Box::new(AStarRouter::with_potential(graph.clone(), ZeroPotential)),
+            Box::new(AStarRouter::with_potential(
+                graph.clone(),
+                GeometricPotential::new(&graph),
+            )),
+            Box::new(AStarRouter::with_potential(
+                graph.clone(),
+                LandmarkPotential::new(&graph, landmark_database),
+            )),
+        ];
+
+        for router in routers.iter_mut() {
+            assert_same_routes(&mut dijkstra, router.as_mut(), 81);
+        }
+    }
+}
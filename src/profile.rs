@@ -0,0 +1,324 @@
+//! Which ways a mode of transport may use and how fast.
+
+use osmpbfreader::Tags;
+
+use crate::maxspeed::parse_maxspeed;
+use crate::road_network::{oneway_from_tags, Oneway};
+
+/// Speed in km/h along the node sequence of a way and against it, None where it may not be used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WaySpeeds {
+    /// Along the node sequence.
+    pub forward_kmh: Option<f32>,
+    /// Against the node sequence.
+    pub backward_kmh: Option<f32>,
+}
+
+/// Decides which ways a mode of transport may use, in which direction and how fast.
+pub trait Profile {
+    /// Whether the way can be used at all.
+    fn is_accessible(&self, tags: &Tags) -> bool;
+
+    /// Speed along the way and against it in km/h, only asked for accessible ways.
+    fn speeds_kmh(&self, tags: &Tags) -> (f32, f32);
+
+    /// The directions the way may be used in.
+    fn oneway(&self, tags: &Tags) -> Oneway;
+
+    /// Combines the three answers above, None if the way cannot be used at all.
+    fn way_speeds(&self, tags: &Tags) -> Option<WaySpeeds> {
+        if !self.is_accessible(tags) {
+            return None;
+        }
+
+        let (forward_kmh, backward_kmh) = self.speeds_kmh(tags);
+        let oneway = self.oneway(tags);
+
+        Some(WaySpeeds {
+            forward_kmh: oneway.allows_forward().then_some(forward_kmh),
+            backward_kmh: oneway.allows_backward().then_some(backward_kmh),
+        })
+    }
+}
+
+//access tags from the most general to the most specific, the most specific one which is set wins
+//None if none of them are set
+fn access_allowed(tags: &Tags, keys: &[&str]) -> Option<bool> {
+    keys.iter()
+        .rev()
+        .find_map(|key| match tags.get(*key)?.as_str() {
+            "yes" | "designated" | "permissive" | "destination" => Some(true),
+            "no" | "private" | "agricultural" | "forestry" | "delivery" | "use_sidepath" => {
+                Some(false)
+            }
+            _ => None,
+        })
+}
+
+fn highway(tags: &Tags) -> &str {
+    tags.get("highway")
+        .map(|value| value.as_str())
+        .unwrap_or("")
+}
+
+/// Motor cars, speeds from the posted limit or the class of the road.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CarProfile;
+
+impl CarProfile {
+    /// Speed on a road of the `highway` class without a posted limit, None if cars may not use it.
+    pub fn highway_speed_kmh(highway: &str) -> Option<u32> {
+        match highway {
+            "motorway" => Some(110),
+            "trunk" => Some(110),
+            "primary" => Some(70),
+            "secondary" => Some(60),
+            "tertiary" => Some(50),
+            "motorway_link" => Some(50),
+            "trunk_link" => Some(50),
+            "primary_link" => Some(50),
+            "secondary_link" => Some(50),
+            "road" => Some(40),
+            "unclassified" => Some(40),
+            "residential" => Some(30),
+            "service" => Some(20),
+            "living_street" => Some(10),
+            _ => None,
+        }
+    }
+}
+
+impl Profile for CarProfile {
+    fn is_accessible(&self, tags: &Tags) -> bool {
+        CarProfile::highway_speed_kmh(highway(tags)).is_some()
+            && access_allowed(tags, &["access", "vehicle", "motor_vehicle", "motorcar"])
+                .unwrap_or(true)
+    }
+
+    /// Posted limits (maxspeed, maxspeed:forward, maxspeed:backward) win over the default of the highway class.
+    fn speeds_kmh(&self, tags: &Tags) -> (f32, f32) {
+        let highway_default = CarProfile::highway_speed_kmh(highway(tags)).unwrap_or(0) as f32;
+
+        let posted = |key: &str| {
+            tags.get(key)
+                .and_then(|value| parse_maxspeed(value))
+                .map(|maxspeed| maxspeed.kmh())
+        };
+
+        let both_directions = posted("maxspeed");
+
+        (
+            posted("maxspeed:forward")
+                .or(both_directions)
+                .unwrap_or(highway_default),
+            posted("maxspeed:backward")
+                .or(both_directions)
+                .unwrap_or(highway_default),
+        )
+    }
+
+    fn oneway(&self, tags: &Tags) -> Oneway {
+        oneway_from_tags(tags)
+    }
+}
+
+/// Bicycles, at a steady pace which depends on the kind of way.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BicycleProfile;
+
+impl Profile for BicycleProfile {
+    fn is_accessible(&self, tags: &Tags) -> bool {
+        let by_class = match highway(tags) {
+            "cycleway" | "path" | "track" | "living_street" | "residential" | "service"
+            | "unclassified" | "road" | "tertiary" | "tertiary_link" | "secondary"
+            | "secondary_link" | "primary" | "primary_link" => true,
+            //only where cycling is explicitly allowed
+            "footway" | "pedestrian" | "bridleway" => false,
+            //never on motorways, everything else is not a way at all
+            _ => return false,
+        };
+
+        access_allowed(tags, &["access", "vehicle", "bicycle"]).unwrap_or(by_class)
+    }
+
+    fn speeds_kmh(&self, tags: &Tags) -> (f32, f32) {
+        let speed = match highway(tags) {
+            "footway" | "pedestrian" => 6.0,
+            "track" | "path" | "bridleway" => 12.0,
+            "living_street" => 10.0,
+            _ => 18.0,
+        };
+
+        (speed, speed)
+    }
+
+    fn oneway(&self, tags: &Tags) -> Oneway {
+        let contraflow = tags.contains("oneway:bicycle", "no")
+            || tags
+                .get("cycleway")
+                .is_some_and(|value| value.starts_with("opposite"));
+
+        match tags.get("oneway:bicycle").map(|value| value.as_str()) {
+            Some("yes") => Oneway::Forward,
+            _ if contraflow => Oneway::No,
+            _ => oneway_from_tags(tags),
+        }
+    }
+}
+
+/// Pedestrians, walking at 5 km/h in both directions of every way.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FootProfile;
+
+impl Profile for FootProfile {
+    fn is_accessible(&self, tags: &Tags) -> bool {
+        let by_class = match highway(tags) {
+            "footway" | "pedestrian" | "path" | "steps" | "track" | "living_street"
+            | "residential" | "service" | "unclassified" | "road" | "tertiary"
+            | "tertiary_link" | "secondary" | "secondary_link" | "primary" | "primary_link"
+            | "cycleway" | "bridleway" => true,
+            "trunk" | "trunk_link" => false,
+            _ => return false,
+        };
+
+        access_allowed(tags, &["access", "foot"]).unwrap_or(by_class)
+    }
+
+    fn speeds_kmh(&self, tags: &Tags) -> (f32, f32) {
+        match highway(tags) {
+            "steps" => (2.0, 2.0),
+            _ => (5.0, 5.0),
+        }
+    }
+
+    //oneway streets are for vehicles
+    fn oneway(&self, tags: &Tags) -> Oneway {
+        match tags.get("oneway:foot").map(|value| value.as_str()) {
+            Some("yes") => Oneway::Forward,
+            _ => Oneway::No,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::road_network::tests::tags;
+
+    fn speeds(profile: &dyn Profile, pairs: &[(&str, &str)]) -> Option<(Option<f32>, Option<f32>)> {
+        profile
+            .way_speeds(&tags(pairs))
+            .map(|speeds| (speeds.forward_kmh, speeds.backward_kmh))
+    }
+
+    #[test]
+    fn car_speeds_from_maxspeed() {
+        let car = |pairs: &[(&str, &str)]| speeds(&CarProfile, pairs);
+
+        assert_eq!(
+            car(&[("highway", "residential")]),
+            Some((Some(30.0), Some(30.0)))
+        );
+        assert_eq!(
+            car(&[("highway", "primary"), ("maxspeed", "DE:rural")]),
+            Some((Some(100.0), Some(100.0)))
+        );
+        assert_eq!(
+            car(&[
+                ("highway", "primary"),
+                ("maxspeed", "80"),
+                ("maxspeed:backward", "50")
+            ]),
+            Some((Some(80.0), Some(50.0)))
+        );
+        assert_eq!(
+            car(&[("highway", "secondary"), ("maxspeed:forward", "40")]),
+            Some((Some(40.0), Some(60.0)))
+        );
+        assert_eq!(
+            car(&[("highway", "motorway"), ("maxspeed", "none")]),
+            Some((Some(130.0), None))
+        );
+        //malformed, falls back to the highway class
+        assert_eq!(
+            car(&[("highway", "tertiary"), ("maxspeed", "signals")]),
+            Some((Some(50.0), Some(50.0)))
+        );
+        assert_eq!(
+            car(&[("highway", "service")]),
+            Some((Some(20.0), Some(20.0)))
+        );
+        assert_eq!(car(&[("highway", "footway"), ("maxspeed", "50")]), None);
+        assert_eq!(car(&[("highway", "unsurfaced")]), None);
+        assert_eq!(
+            car(&[("highway", "residential"), ("access", "private")]),
+            None
+        );
+        assert_eq!(
+            car(&[
+                ("highway", "residential"),
+                ("access", "no"),
+                ("motor_vehicle", "destination")
+            ]),
+            Some((Some(30.0), Some(30.0)))
+        );
+    }
+
+    #[test]
+    fn bicycle_ways() {
+        let bicycle = |pairs: &[(&str, &str)]| speeds(&BicycleProfile, pairs);
+
+        assert_eq!(
+            bicycle(&[("highway", "cycleway")]),
+            Some((Some(18.0), Some(18.0)))
+        );
+        assert_eq!(bicycle(&[("highway", "motorway")]), None);
+        assert_eq!(bicycle(&[("highway", "footway")]), None);
+        assert_eq!(
+            bicycle(&[("highway", "footway"), ("bicycle", "yes")]),
+            Some((Some(6.0), Some(6.0)))
+        );
+        assert_eq!(
+            bicycle(&[("highway", "residential"), ("oneway", "yes")]),
+            Some((Some(18.0), None))
+        );
+        assert_eq!(
+            bicycle(&[
+                ("highway", "residential"),
+                ("oneway", "yes"),
+                ("oneway:bicycle", "no")
+            ]),
+            Some((Some(18.0), Some(18.0)))
+        );
+        assert_eq!(
+            bicycle(&[
+                ("highway", "residential"),
+                ("oneway", "yes"),
+                ("cycleway", "opposite_lane")
+            ]),
+            Some((Some(18.0), Some(18.0)))
+        );
+    }
+
+    #[test]
+    fn foot_ways() {
+        let foot = |pairs: &[(&str, &str)]| speeds(&FootProfile, pairs);
+
+        assert_eq!(
+            foot(&[("highway", "footway")]),
+            Some((Some(5.0), Some(5.0)))
+        );
+        assert_eq!(foot(&[("highway", "steps")]), Some((Some(2.0), Some(2.0))));
+        assert_eq!(
+            foot(&[("highway", "primary"), ("oneway", "yes")]),
+            Some((Some(5.0), Some(5.0)))
+        );
+        assert_eq!(foot(&[("highway", "motorway")]), None);
+        assert_eq!(foot(&[("highway", "trunk")]), None);
+        assert_eq!(
+            foot(&[("highway", "trunk"), ("foot", "yes")]),
+            Some((Some(5.0), Some(5.0)))
+        );
+        assert_eq!(foot(&[("highway", "path"), ("foot", "no")]), None);
+    }
+}
@@ -1,60 +1,175 @@
-use std::collections::{HashMap, HashSet};
-use std::error::Error;
-use std::sync::Arc;
+//! The road network as read from OpenStreetMap, before it is compacted.
+
+use crate::metric::CostUnit;
+use geoutils::Location;
 use rand::Rng;
+use std::collections::{HashMap, HashSet};
 
+/// A directed graph keyed by OSM node id, easy to build up edge by edge.
 #[derive(Default, Clone, Debug)]
 pub struct RoadNetwork {
-    // vertex id is an integer (i64)
-    // edge is HashMap of the <NodeId, Cost>
+    /// OSM node ids of every routable node.
     pub nodes: HashSet<i64>,
+    /// Tail -> (head -> cost) of every edge.
     pub edges: HashMap<i64, HashMap<i64, u32>>,
+    /// Lat/lon of every routable node.
+    pub coordinates: HashMap<i64, Location>,
+    /// What the edge costs are measured in.
+    pub unit: CostUnit,
+    /// Fastest speed the profile allows on any edge, 0 if unknown.
+    pub max_speed_kmh: f32,
 }
 
 impl RoadNetwork {
-    
-pub fn get_random_node(self: &RoadNetwork) -> i64 {
-    let mut vec: Vec<i64> = self.nodes.iter().cloned().collect();
-    
+    /// A network without nodes.
+    pub fn new() -> RoadNetwork {
+        RoadNetwork {
+            nodes: HashSet::new(),
+            edges: HashMap::new(),
+            coordinates: HashMap::new(),
+            unit: CostUnit::default(),
+            max_speed_kmh: 0.0,
+        }
+    }
+
+    /// OSM node id of a node picked uniformly at random.
+    pub fn get_random_node(self: &RoadNetwork) -> i64 {
+        let vec: Vec<i64> = self.nodes.iter().cloned().collect();
+
         let index = rand::thread_rng().gen_range(0..vec.len());
-       vec[index]
-    
-}
+        vec[index]
+    }
 
+    /// Lat/lon of the OSM node id, None if it is not known.
+    pub fn coordinate(&self, node: i64) -> Option<Location> {
+        self.coordinates.get(&node).copied()
+    }
+
+    /// Adds the edge `tail -> head`, both nodes are added to the node set.
+    /// If the edge already exists (parallel ways), the cheaper cost is kept.
+    pub fn insert_edge(&mut self, tail: i64, head: i64, cost: u32) {
+        self.nodes.insert(tail);
+        self.nodes.insert(head);
+
+        self.edges
+            .entry(tail)
+            .or_default()
+            .entry(head)
+            .and_modify(|existing_cost| *existing_cost = (*existing_cost).min(cost))
+            .or_insert(cost);
+    }
 }
 
+/// A way the profile allows, with only what the import needs to turn it into edges.
 pub struct SimplifiedWay {
+    /// OSM way id.
     pub id: i64,
-    pub highway_speed_m_per_s: f32,
+    /// Speed along the node sequence, None if the way cannot be used in that direction.
+    pub forward_speed_kmh: Option<f32>,
+    /// Speed against the node sequence, None if the way cannot be used in that direction.
+    pub backward_speed_kmh: Option<f32>,
+    /// OSM node ids of the way in order.
     pub node_sequence: Vec<i64>,
+    /// Only kept if the metric needs them.
+    pub tags: osmpbfreader::Tags,
 }
 
-pub fn speed_from_way_kmh(way: &osmpbfreader::objects::Way) -> Option<u32> {
-    let tags = way.tags.clone();
-    let highway = tags
-        .into_inner()
-        .into_iter()
-        .find(|(key, _)| key == &"highway");
-
-    match highway {
-        Some(highway) => match highway.1.as_str() {
-            "motorway" => Some(110),
-            "trunk" => Some(110),
-            "primary" => Some(70),
-            "secondary" => Some(60),
-            "tertiary" => Some(50),
-            "motorway_link" => Some(50),
-            "trunk_link" => Some(50),
-            "primary_link" => Some(50),
-            "secondary_link" => Some(50),
-            "road" => Some(40),
-            "unclassified" => Some(40),
-            "residential" => Some(30),
-            "unsurfaced" => Some(30),
-            "living_street" => Some(10),
-            "service" => Some(5),
-            _ => None,
-        },
-        None => None,
+/// The directions a way may be driven in, relative to the order of its nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Oneway {
+    /// Both directions.
+    No,
+    /// Only along the node sequence.
+    Forward,
+    /// Only against the node sequence.
+    Backward,
+    /// The direction changes over the day, so neither direction can be used safely.
+    Reversible,
+}
+
+impl Oneway {
+    /// Whether the way may be driven along its node sequence.
+    pub fn allows_forward(&self) -> bool {
+        matches!(self, Oneway::No | Oneway::Forward)
+    }
+
+    /// Whether the way may be driven against its node sequence.
+    pub fn allows_backward(&self) -> bool {
+        matches!(self, Oneway::No | Oneway::Backward)
+    }
+}
+
+/// Reads the `oneway` tag, roundabouts and motorways are oneway unless tagged otherwise.
+pub fn oneway_from_tags(tags: &osmpbfreader::Tags) -> Oneway {
+    match tags.get("oneway").map(|value| value.as_str()) {
+        Some("yes") | Some("true") | Some("1") => Oneway::Forward,
+        Some("-1") | Some("reverse") => Oneway::Backward,
+        Some("reversible") | Some("alternating") => Oneway::Reversible,
+        Some("no") | Some("false") | Some("0") => Oneway::No,
+        //not tagged or an unknown value, some roads are oneway without saying so
+        _ => {
+            if tags.contains("junction", "roundabout")
+                || tags.contains("junction", "circular")
+                || tags.contains("highway", "motorway")
+            {
+                Oneway::Forward
+            } else {
+                Oneway::No
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn tags(pairs: &[(&str, &str)]) -> osmpbfreader::Tags {
+        pairs
+            .iter()
+            .map(|(key, value)| ((*key).into(), (*value).into()))
+            .collect()
+    }
+
+    #[test]
+    fn oneway_tags() {
+        let oneway = |pairs: &[(&str, &str)]| oneway_from_tags(&tags(pairs));
+
+        assert_eq!(oneway(&[("highway", "residential")]), Oneway::No);
+        assert_eq!(oneway(&[("oneway", "yes")]), Oneway::Forward);
+        assert_eq!(oneway(&[("oneway", "true")]), Oneway::Forward);
+        assert_eq!(oneway(&[("oneway", "1")]), Oneway::Forward);
+        assert_eq!(oneway(&[("oneway", "-1")]), Oneway::Backward);
+        assert_eq!(oneway(&[("oneway", "reversible")]), Oneway::Reversible);
+        assert_eq!(oneway(&[("junction", "roundabout")]), Oneway::Forward);
+        assert_eq!(oneway(&[("highway", "motorway")]), Oneway::Forward);
+        assert_eq!(
+            oneway(&[("highway", "motorway"), ("oneway", "no")]),
+            Oneway::No
+        );
+        assert_eq!(
+            oneway(&[("highway", "primary"), ("oneway", "unknown")]),
+            Oneway::No
+        );
+
+        assert!(Oneway::Backward.allows_backward());
+        assert!(!Oneway::Backward.allows_forward());
+        assert!(!Oneway::Reversible.allows_forward());
+        assert!(!Oneway::Reversible.allows_backward());
+    }
+
+    #[test]
+    fn parallel_ways_keep_the_cheaper_edge() {
+        let mut graph = RoadNetwork::new();
+
+        graph.insert_edge(1, 2, 9);
+        graph.insert_edge(1, 2, 4);
+        graph.insert_edge(1, 2, 6);
+        graph.insert_edge(2, 1, 7);
+
+        assert_eq!(graph.edges[&1][&2], 4);
+        assert_eq!(graph.edges[&2][&1], 7);
+        assert_eq!(graph.edges[&1].len(), 1);
+        assert_eq!(graph.nodes, HashSet::from([1, 2]));
     }
 }
@@ -0,0 +1,225 @@
+//! The common interface of all point to point routers.
+
+use std::sync::Arc;
+
+use crate::{
+    precompute_landmark_distances, CompactGraph, DijkstrasAlgorithm, GeometricPotential,
+    LandmarkDatabase, LandmarkPotential, LandmarkSelection, Potential, RoutingError, SearchConfig,
+    ShortestPath, ZeroPotential,
+};
+
+/// A point to point query between two OSM node ids.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RouteQuery {
+    /// OSM node id the route starts at.
+    pub source: i64,
+    /// OSM node id the route ends at.
+    pub target: i64,
+}
+
+/// The answer to a [`RouteQuery`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RouteResult {
+    /// The route the router found.
+    pub path: ShortestPath,
+    /// How many nodes the search settled, a measure of the work it did.
+    pub settled_nodes: usize,
+}
+
+/// Answers point to point queries on a road network.
+pub trait Router {
+    /// Fails with [`RoutingError::UnknownNode`] if either node is not in the graph
+    /// and with [`RoutingError::UnreachableTarget`] if there is no path.
+    fn route(&mut self, query: &RouteQuery) -> Result<RouteResult, RoutingError>;
+}
+
+impl Router for DijkstrasAlgorithm {
+    fn route(&mut self, query: &RouteQuery) -> Result<RouteResult, RoutingError> {
+        //prepare the heuristic (if any) for this query, search() would not
+        let mut heuristic = self.heuristic.take();
+
+        let result = match &mut heuristic {
+            Some(heuristic) => route_with_potential(self, heuristic.as_mut(), query),
+            None => route_with_potential(self, &mut ZeroPotential, query),
+        };
+
+        self.heuristic = heuristic;
+
+        result
+    }
+}
+
+//looks both nodes up, search returns the path between their indices and how many nodes it settled,
+//a missing path becomes an error
+pub(crate) fn route_by_index(
+    graph: &CompactGraph,
+    query: &RouteQuery,
+    search: impl FnOnce(u32, u32) -> Result<(Option<ShortestPath>, usize), RoutingError>,
+) -> Result<RouteResult, RoutingError> {
+    let source = graph
+        .index_of(query.source)
+        .ok_or(RoutingError::UnknownNode(query.source))?;
+    let target = graph
+        .index_of(query.target)
+        .ok_or(RoutingError::UnknownNode(query.target))?;
+
+    let (path, settled_nodes) = search(source, target)?;
+
+    let path = path.ok_or(RoutingError::UnreachableTarget {
+        source: query.source,
+        target: query.target,
+    })?;
+
+    Ok(RouteResult {
+        path,
+        settled_nodes,
+    })
+}
+
+fn route_with_potential<P: Potential + ?Sized>(
+    dijkstra: &DijkstrasAlgorithm,
+    potential: &mut P,
+    query: &RouteQuery,
+) -> Result<RouteResult, RoutingError> {
+    route_by_index(&dijkstra.graph, query, |source, target| {
+        potential.set_query(&dijkstra.graph, source, target)?;
+
+        let result =
+            dijkstra.search_with_potential(source, &SearchConfig::to_target(target), potential)?;
+
+        Ok((
+            result.path_to(&dijkstra.graph, target)?,
+            result.settled.len(),
+        ))
+    })
+}
+
+/// A* search guided by a [`Potential`].
+pub struct AStarRouter<P: Potential> {
+    dijkstra: DijkstrasAlgorithm,
+    potential: P,
+}
+
+/// A* search guided by landmark lower bounds (ALT).
+pub type AltRouter = AStarRouter<LandmarkPotential>;
+
+/// A* search guided by the straight line distance to the target, needs no preprocessing.
+pub type GeometricRouter = AStarRouter<GeometricPotential>;
+
+impl<P: Potential> AStarRouter<P> {
+    /// `potential` has to be built for `graph`.
+    pub fn with_potential(graph: CompactGraph, potential: P) -> AStarRouter<P> {
+        AStarRouter {
+            dijkstra: DijkstrasAlgorithm::new(graph),
+            potential,
+        }
+    }
+
+    /// The graph the router searches.
+    pub fn graph(&self) -> &CompactGraph {
+        &self.dijkstra.graph
+    }
+
+    /// The potential which guides the searches.
+    pub fn potential(&self) -> &P {
+        &self.potential
+    }
+}
+
+impl AStarRouter<LandmarkPotential> {
+    /// Picks `number_of_landmarks` landmarks with the default selection and precomputes their distances.
+    pub fn new(graph: CompactGraph, number_of_landmarks: usize) -> AltRouter {
+        let landmark_database = precompute_landmark_distances(
+            &graph,
+            number_of_landmarks,
+            LandmarkSelection::default(),
+        );
+
+        AltRouter::with_landmark_database(graph, landmark_database)
+    }
+
+    /// Uses landmark distances which were already computed on `graph`.
+    pub fn with_landmark_database(
+        graph: CompactGraph,
+        landmark_database: LandmarkDatabase,
+    ) -> AltRouter {
+        let potential = LandmarkPotential::new(&graph, Arc::new(landmark_database));
+
+        AltRouter::with_potential(graph, potential)
+    }
+
+    /// Bounds each query by the `count` best landmarks for it instead of all of them,
+    /// see [`LandmarkPotential::with_active_landmarks`].
+    pub fn with_active_landmarks(mut self, count: usize, add_during_search: bool) -> AltRouter {
+        self.potential
+            .set_active_landmarks(count, add_during_search);
+
+        self
+    }
+}
+
+impl AStarRouter<GeometricPotential> {
+    /// A* with straight line bounds, see [`GeometricPotential::new`].
+    pub fn new(graph: CompactGraph) -> GeometricRouter {
+        let potential = GeometricPotential::new(&graph);
+
+        GeometricRouter::with_potential(graph, potential)
+    }
+}
+
+impl<P: Potential> Router for AStarRouter<P> {
+    //the bounds are computed while the search runs, only for the nodes it reaches
+    fn route(&mut self, query: &RouteQuery) -> Result<RouteResult, RoutingError> {
+        route_with_potential(&self.dijkstra, &mut self.potential, query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{assert_same_routes, grid_test_graph, oneway_grid_test_graph};
+
+    #[test]
+    fn alt_matches_dijkstra() {
+        let graph = CompactGraph::from(&grid_test_graph(12));
+
+        let mut dijkstra = DijkstrasAlgorithm::new(graph.clone());
+        let mut alt = AltRouter::new(graph, 4);
+
+        let (settled_dijkstra, settled_alt) = assert_same_routes(&mut dijkstra, &mut alt, 144);
+        assert!(settled_alt <= settled_dijkstra);
+
+        assert!(matches!(
+            alt.route(&RouteQuery {
+                source: 0,
+                target: 1000
+            }),
+            Err(RoutingError::UnknownNode(1000))
+        ));
+    }
+
+    #[test]
+    fn alt_matches_dijkstra_on_oneway_streets() {
+        let graph = CompactGraph::from(&oneway_grid_test_graph(12));
+
+        assert_same_routes(
+            &mut DijkstrasAlgorithm::new(graph.clone()),
+            &mut AltRouter::new(graph, 40),
+            144,
+        );
+    }
+
+    #[test]
+    fn active_landmarks_match_dijkstra() {
+        let graph = CompactGraph::from(&oneway_grid_test_graph(12));
+        let mut dijkstra = DijkstrasAlgorithm::new(graph.clone());
+
+        for mut alt in [
+            AltRouter::new(graph.clone(), 16),
+            AltRouter::new(graph.clone(), 16).with_active_landmarks(2, false),
+            AltRouter::new(graph, 16).with_active_landmarks(1, true),
+        ] {
+            assert_same_routes(&mut dijkstra, &mut alt, 144);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! Transit node routing, table lookups for long distance queries.
+
+use std::collections::HashSet;
+
+use crate::matrix::bucket_costs;
+use crate::{BastPriorityValue, ContractionHierarchy, RoutingError};
+
+/// Transit node routing on top of a [`ContractionHierarchy`] (Arz et al., "Transit Node Routing Reconsidered").
+///
+/// The most important nodes of the hierarchy are the transit nodes, with a table of the distances
+/// between all of them. Each node knows the transit nodes its upward searches reach first, its access nodes,
+/// so a long query is a few table lookups. A query is local, and answered by the hierarchy, if the upward
+/// searches of both ends meet below the transit nodes, then its path may not pass any of them.
+pub struct TransitNodeRouting {
+    /// Answers the local queries.
+    pub hierarchy: ContractionHierarchy,
+    //node index of each transit node, the rows and columns of the table
+    transit_nodes: Vec<u32>,
+    //row of each node in the table, None for the other nodes
+    transit_index: Vec<Option<u32>>,
+    //table[i * transit_nodes.len() + j] is the distance from transit_nodes[i] to transit_nodes[j]
+    table: Vec<BastPriorityValue>,
+    //(row in the table, distance from the node) of the access nodes of each node
+    forward_access: Vec<Vec<(u32, u32)>>,
+    //(row in the table, distance to the node)
+    backward_access: Vec<Vec<(u32, u32)>>,
+}
+
+impl TransitNodeRouting {
+    /// Takes the `number_of_transit_nodes` nodes of highest rank as transit nodes.
+    pub fn new(
+        hierarchy: ContractionHierarchy,
+        number_of_transit_nodes: usize,
+    ) -> TransitNodeRouting {
+        let number_of_nodes = hierarchy.graph.number_of_nodes();
+
+        let mut transit_nodes: Vec<u32> = (0..number_of_nodes as u32).collect();
+        transit_nodes.sort_unstable_by_key(|node| std::cmp::Reverse(hierarchy.rank(*node)));
+        transit_nodes.truncate(number_of_transit_nodes);
+
+        let mut transit_index = vec![None; number_of_nodes];
+        for (index, node) in transit_nodes.iter().enumerate() {
+            transit_index[*node as usize] = Some(index as u32);
+        }
+
+        let table = bucket_costs(&hierarchy, &transit_nodes, &transit_nodes);
+
+        let mut transit_node_routing = TransitNodeRouting {
+            hierarchy,
+            transit_nodes,
+            transit_index,
+            table,
+            forward_access: vec![],
+            backward_access: vec![],
+        };
+
+        for forward in [true, false] {
+            let access: Vec<Vec<(u32, u32)>> = (0..number_of_nodes as u32)
+                .map(|node| transit_node_routing.access_nodes(node, forward))
+                .collect();
+
+            if forward {
+                transit_node_routing.forward_access = access;
+            } else {
+                transit_node_routing.backward_access = access;
+            }
+        }
+
+        transit_node_routing
+    }
+
+    /// Node indices of the transit nodes.
+    pub fn transit_nodes(&self) -> &[u32] {
+        &self.transit_nodes
+    }
+
+    /// Access nodes of all nodes in both directions together.
+    pub fn number_of_access_nodes(&self) -> usize {
+        self.forward_access
+            .iter()
+            .chain(self.backward_access.iter())
+            .map(|access| access.len())
+            .sum()
+    }
+
+    /// True if the query between the node indices `source` and `target` is not answered by the table.
+    pub fn is_local(&self, source: u32, target: u32) -> Result<bool, RoutingError> {
+        self.check_node(source)?;
+        self.check_node(target)?;
+
+        Ok(self.upward_searches_meet(source, target))
+    }
+
+    /// Cost of the shortest path between the node indices `source` and `target`.
+    pub fn distance(&self, source: u32, target: u32) -> Result<BastPriorityValue, RoutingError> {
+        if self.is_local(source, target)? {
+            return self.hierarchy.distance(source, target);
+        }
+
+        let mut best = BastPriorityValue::Infinity;
+
+        //the highest node of the shortest path is a transit node, the path passes an access node of each end
+        for (from, to_access) in &self.forward_access[source as usize] {
+            for (to, from_access) in &self.backward_access[target as usize] {
+                let through =
+                    BastPriorityValue::Some(to_access + from_access) + self.table_entry(*from, *to);
+
+                best = best.min(through);
+            }
+        }
+
+        Ok(best)
+    }
+
+    fn check_node(&self, node: u32) -> Result<(), RoutingError> {
+        if node as usize >= self.transit_index.len() {
+            return Err(RoutingError::NodeIndexOutOfRange(node));
+        }
+
+        Ok(())
+    }
+
+    fn table_entry(&self, from: u32, to: u32) -> BastPriorityValue {
+        self.table[from as usize * self.transit_nodes.len() + to as usize]
+    }
+
+    fn is_transit_node(&self, node: u32) -> bool {
+        self.transit_index[node as usize].is_some()
+    }
+
+    //the transit nodes the upward search from node reaches before any other transit node,
+    //without the ones which are reached shorter through another access node
+    fn access_nodes(&self, node: u32, forward: bool) -> Vec<(u32, u32)> {
+        let candidates: Vec<(u32, u32)> = self
+            .hierarchy
+            .search_space_below(node, forward, |node| self.is_transit_node(node))
+            .into_iter()
+            .filter_map(|(reached, distance)| {
+                self.transit_index[reached as usize].map(|index| (index, distance))
+            })
+            .collect();
+
+        candidates
+            .iter()
+            .filter(|(index, distance)| {
+                !candidates.iter().any(|(other, other_distance)| {
+                    let through_other = if forward {
+                        self.table_entry(*other, *index)
+                    } else {
+                        self.table_entry(*index, *other)
+                    };
+
+                    other != index
+                        && BastPriorityValue::Some(*other_distance) + through_other
+                            < BastPriorityValue::Some(*distance)
+                })
+            })
+            .copied()
+            .collect()
+    }
+
+    //whether the upward searches from source and target share a node below the transit nodes
+    fn upward_searches_meet(&self, source: u32, target: u32) -> bool {
+        let below = |node: u32, forward: bool| {
+            self.hierarchy
+                .search_space_below(node, forward, |node| self.is_transit_node(node))
+                .into_iter()
+                .map(|(reached, _)| reached)
+                .filter(|reached| !self.is_transit_node(*reached))
+        };
+
+        let from_source: HashSet<u32> = below(source, true).collect();
+
+        below(target, false).any(|reached| from_source.contains(&reached))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{grid_test_graph, oneway_grid_test_graph};
+    use crate::{DijkstrasAlgorithm, SearchConfig};
+
+    #[test]
+    fn transit_nodes_match_dijkstra() {
+        for road_network in [grid_test_graph(12), oneway_grid_test_graph(12)] {
+            let transit_node_routing =
+                TransitNodeRouting::new(ContractionHierarchy::from(&road_network), 12);
+            let graph = &transit_node_routing.hierarchy.graph;
+
+            assert_eq!(transit_node_routing.transit_nodes().len(), 12);
+
+            let mut local = 0;
+
+            for source in (0..144).step_by(3) {
+                let distances =
+                    DijkstrasAlgorithm::search_graph(graph, source, &SearchConfig::default())
+                        .unwrap()
+                        .distances;
+
+                for target in 0..144 {
+                    assert_eq!(
+                        transit_node_routing.distance(source, target).unwrap(),
+                        distances[target as usize]
+                    );
+
+                    if transit_node_routing.is_local(source, target).unwrap() {
+                        local += 1;
+                    }
+                }
+            }
+
+            //both parts of the query are used
+            assert!(local > 0);
+            assert!(local < 48 * 144);
+
+            assert!(matches!(
+                transit_node_routing.distance(144, 0),
+                Err(RoutingError::NodeIndexOutOfRange(144))
+            ));
+        }
+    }
+
+    #[test]
+    fn transit_nodes_are_their_own_access_nodes() {
+        let transit_node_routing =
+            TransitNodeRouting::new(ContractionHierarchy::from(&grid_test_graph(8)), 6);
+
+        for (index, node) in transit_node_routing.transit_nodes().iter().enumerate() {
+            assert_eq!(
+                transit_node_routing.forward_access[*node as usize],
+                vec![(index as u32, 0)]
+            );
+            assert!(!transit_node_routing.is_local(*node, *node).unwrap());
+            assert_eq!(
+                transit_node_routing.distance(*node, *node).unwrap(),
+                BastPriorityValue::Some(0)
+            );
+        }
+    }
+}